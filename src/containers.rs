@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::PathBuf;
+
+use gio::prelude::*;
+use gio::{glib, AppInfo};
+use serde_json::Value;
+
+use crate::launch::BrowserFamily;
+
+
+/// Names of the built in containers by their localization ids, they have no `name` field.
+const DEFAULT_CONTAINERS: [(&str, &str); 4] = [
+    ("userContextPersonal.label", "Personal"),
+    ("userContextWork.label", "Work"),
+    ("userContextBanking.label", "Banking"),
+    ("userContextShopping.label", "Shopping"),
+];
+
+
+/// Url opening `url` in the container `name`, it is handled by the "Open external links in
+/// a container" add-on.
+pub fn container_url(name: &str, url: &str) -> String {
+    format!(
+        "ext+container:name={}&url={}",
+        glib::Uri::escape_string(name, None, false),
+        glib::Uri::escape_string(url, None, false),
+    )
+}
+
+
+/// Directory with `profiles.ini` of a Firefox based browser, flatpak and snap installs keep it
+/// in their own home.
+fn profiles_dir(app: &AppInfo) -> PathBuf {
+    let home = glib::home_dir();
+    let id = app.id().map(String::from).unwrap_or_default();
+    let commandline = app.commandline().unwrap_or_default().to_string_lossy().to_lowercase();
+    if commandline.contains("flatpak") {
+        let flatpak_id = id.trim_end_matches(".desktop");
+        return home.join(".var/app").join(flatpak_id).join(".mozilla/firefox");
+    }
+    if commandline.contains("/snap/") {
+        return home.join("snap/firefox/common/.mozilla/firefox");
+    }
+    if id.to_lowercase().contains("librewolf") {
+        return home.join(".librewolf");
+    }
+    if id.to_lowercase().contains("waterfox") {
+        return home.join(".waterfox");
+    }
+    home.join(".mozilla/firefox")
+}
+
+
+/// Finds the profile a browser opens by default, preferring the default of the install.
+fn default_profile(app: &AppInfo) -> Option<PathBuf> {
+    let dir = profiles_dir(app);
+    let ini = glib::KeyFile::new();
+    ini.load_from_file(dir.join("profiles.ini"), glib::KeyFileFlags::NONE).ok()?;
+    let groups = ini.groups();
+    let install_default = groups.iter()
+        .filter(|group| group.starts_with("Install"))
+        .find_map(|group| ini.string(group, "Default").ok());
+    if let Some(path) = install_default {
+        return Some(dir.join(path.as_str()));
+    }
+    let profile = groups.iter()
+        .filter(|group| group.starts_with("Profile"))
+        .find(|group| ini.integer(group, "Default").is_ok_and(|default| default == 1))?;
+    let path = ini.string(profile, "Path").ok()?;
+    match ini.integer(profile, "IsRelative").unwrap_or(1) {
+        0 => Some(PathBuf::from(path.as_str())),
+        _ => Some(dir.join(path.as_str())),
+    }
+}
+
+
+/// Names of containers of the default profile of `app`, empty if it is not a Firefox based browser.
+pub fn containers(app: &AppInfo) -> Vec<String> {
+    if BrowserFamily::of(app) != Some(BrowserFamily::Firefox) {
+        return Vec::new();
+    }
+    let Some(profile) = default_profile(app) else { return Vec::new() };
+    let Ok(text) = fs::read_to_string(profile.join("containers.json")) else { return Vec::new() };
+    parse_containers(&text)
+}
+
+
+/// Names of the containers users can open tabs in listed in the text of `containers.json`.
+fn parse_containers(text: &str) -> Vec<String> {
+    let Ok(root) = serde_json::from_str::<Value>(text) else { return Vec::new() };
+    let Some(identities) = root.get("identities").and_then(Value::as_array) else { return Vec::new() };
+    identities.iter()
+        .filter(|identity| identity.get("public").and_then(Value::as_bool) == Some(true))
+        .filter_map(|identity| {
+            match (identity.get("name").and_then(Value::as_str), identity.get("l10nID").and_then(Value::as_str)) {
+                (Some(name), _) => Some(name.to_string()),
+                (_, Some(l10n_id)) => DEFAULT_CONTAINERS.iter()
+                    .find(|(id, _)| *id == l10n_id)
+                    .map(|(_, name)| name.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_containers() {
+        let text = r#"{
+            "version": 5,
+            "lastUserContextId": 7,
+            "identities": [
+                {"userContextId": 1, "public": true, "icon": "fingerprint", "color": "blue", "l10nID": "userContextPersonal.label", "accessKey": "userContextPersonal.accesskey"},
+                {"userContextId": 2, "public": true, "icon": "briefcase", "color": "orange", "l10nId": "ignored", "l10nID": "userContextWork.label"},
+                {"userContextId": 5, "public": false, "icon": "", "color": "", "name": "userContextIdInternal.thumbnail", "accessKey": ""},
+                {"userContextId": 6, "public": true, "icon": "circle", "color": "red", "name": "Caf\u00e9 \ud83c\udf55 \"quoted\""},
+                {"userContextId": 7, "public": true, "icon": "tree", "color": "green", "name": "Side project"},
+                {"userContextId": 8, "public": true, "l10nID": "userContextUnknown.label"}
+            ]
+        }"#;
+        assert_eq!(parse_containers(text), ["Personal", "Work", "Café 🍕 \"quoted\"", "Side project"]);
+    }
+
+    #[test]
+    fn ignores_broken_containers_json() {
+        assert!(parse_containers("").is_empty());
+        assert!(parse_containers(r#"{"identities": [{"public": true, "name": "Work"}"#).is_empty());
+        assert!(parse_containers(r#"{"identities": {"name": "Work"}}"#).is_empty());
+        assert!(parse_containers("[]").is_empty());
+    }
+}
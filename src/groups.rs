@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use gio::{AppInfo, Settings};
+use itertools::Itertools;
+
+
+/// Rule targets starting with it are names of groups, other targets are ids of browsers.
+const GROUP_PREFIX: &str = "group:";
+
+
+/// Groups of browsers a url is opened in at once, ordered by name, as names and ids of member browsers.
+pub fn load(settings: &Settings) -> Vec<(String, Vec<String>)> {
+    let groups: HashMap<String, Vec<String>> = settings.get("browser-groups");
+    groups.into_iter().sorted_by(|a, b| Ord::cmp(&a.0, &b.0)).collect()
+}
+
+
+pub fn save(settings: &Settings, name: &str, browser_ids: Vec<String>) {
+    let mut groups: HashMap<String, Vec<String>> = settings.get("browser-groups");
+    groups.insert(name.to_string(), browser_ids);
+    let _ = settings.set("browser-groups", groups);
+}
+
+
+pub fn remove(settings: &Settings, name: &str) {
+    let mut groups: HashMap<String, Vec<String>> = settings.get("browser-groups");
+    groups.remove(name);
+    let _ = settings.set("browser-groups", groups);
+}
+
+
+pub fn target(name: &str) -> String {
+    format!("{GROUP_PREFIX}{name}")
+}
+
+
+pub fn group_name(target: &str) -> Option<&str> {
+    target.strip_prefix(GROUP_PREFIX)
+}
+
+
+/// Positions in `browsers` of the browser or of the members of the group `target` refers to.
+pub fn resolve(settings: &Settings, browsers: &[AppInfo], target: &str) -> Vec<usize> {
+    let position = |browser_id: &str| browsers.iter().position(|browser| browser.id().is_some_and(|id| id == browser_id));
+    match group_name(target) {
+        Some(name) => load(settings).into_iter()
+            .find(|(group, _)| group == name)
+            .map(|(_, members)| members.iter().filter_map(|id| position(id)).collect())
+            .unwrap_or_default(),
+        None => position(target).into_iter().collect(),
+    }
+}
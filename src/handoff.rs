@@ -0,0 +1,146 @@
+use gio::prelude::*;
+use gio::{glib, AppInfo, File};
+
+
+/// Link of a web app rewritten for its native client.
+#[derive(Debug, Clone)]
+pub struct Handoff {
+    /// Name of the service, e.g. `Zoom`.
+    pub service: &'static str,
+    pub url: String,
+    /// Application registered for the scheme of `url`.
+    pub app: AppInfo,
+}
+
+
+fn host_is(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{domain}"))
+}
+
+
+fn query_value<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|parameter| parameter.strip_prefix(name)?.strip_prefix('='))
+}
+
+
+/// Rewrites a link of Zoom, Microsoft Teams, Spotify, Slack or Figma to the scheme of its
+/// native client, e.g. `https://zoom.us/j/123` to `zoommtg://zoom.us/join?confno=123`.
+fn native_url(url: &str) -> Option<(&'static str, String)> {
+    let uri = glib::Uri::parse(url, glib::UriFlags::ENCODED).ok()?;
+    if !["http", "https"].contains(&uri.scheme().as_str()) {
+        return None;
+    }
+    let host = uri.host()?.to_lowercase();
+    let path = uri.path().to_string();
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let query = uri.query().map(|query| format!("?{query}")).unwrap_or_default();
+
+    if host_is(&host, "zoom.us") {
+        let ["j", meeting] = segments[..] else { return None };
+        let password = query_value(uri.query().unwrap_or_default().as_str(), "pwd").map(|password| format!("&pwd={password}")).unwrap_or_default();
+        return Some(("Zoom", format!("zoommtg://zoom.us/join?action=join&confno={meeting}{password}")));
+    }
+    if host == "teams.microsoft.com" || host == "teams.live.com" {
+        path.starts_with("/l/").then_some(())?;
+        return Some(("Microsoft Teams", format!("msteams:{path}{query}")));
+    }
+    if host == "open.spotify.com" {
+        let segments: Vec<&str> = segments.into_iter().filter(|segment| !segment.starts_with("intl-")).collect();
+        let [kind, id] = segments[..] else { return None };
+        return Some(("Spotify", format!("spotify:{kind}:{id}")));
+    }
+    if host == "app.slack.com" {
+        let ["client", team, channel, ..] = segments[..] else { return None };
+        return Some(("Slack", format!("slack://channel?team={team}&id={channel}")));
+    }
+    if host_is(&host, "figma.com") {
+        let ["file" | "design" | "proto" | "board", ..] = segments[..] else { return None };
+        return Some(("Figma", format!("figma:/{path}{query}")));
+    }
+    None
+}
+
+
+/// Finds the native client of the web app `file` links to, if it is installed.
+pub fn handoff(file: &File) -> Option<Handoff> {
+    let (service, url) = native_url(&file.uri())?;
+    let scheme = glib::Uri::peek_scheme(&url)?;
+    let app = AppInfo::default_for_uri_scheme(&scheme)?;
+    Some(Handoff { service, url, app })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(service: &'static str, url: &str) -> Option<(&'static str, String)> {
+        Some((service, url.to_string()))
+    }
+
+    #[test]
+    fn rewrites_zoom_meetings() {
+        assert_eq!(native_url("https://zoom.us/j/123456789"), rewritten("Zoom", "zoommtg://zoom.us/join?action=join&confno=123456789"));
+        assert_eq!(
+            native_url("https://us02web.zoom.us/j/123456789?pwd=secret&from=addon"),
+            rewritten("Zoom", "zoommtg://zoom.us/join?action=join&confno=123456789&pwd=secret"),
+        );
+        assert_eq!(
+            native_url("https://zoom.us/j/123456789?pwdx=other"),
+            rewritten("Zoom", "zoommtg://zoom.us/join?action=join&confno=123456789"),
+        );
+        assert_eq!(native_url("https://zoom.us/pricing"), None);
+        assert_eq!(native_url("https://zoom.us/j/123/extra"), None);
+    }
+
+    #[test]
+    fn rewrites_teams_meetings() {
+        assert_eq!(
+            native_url("https://teams.microsoft.com/l/meetup-join/19%3Ameeting/0?context=%7B%7D"),
+            rewritten("Microsoft Teams", "msteams:/l/meetup-join/19%3Ameeting/0?context=%7B%7D"),
+        );
+        assert_eq!(native_url("https://teams.live.com/l/invite/abc"), rewritten("Microsoft Teams", "msteams:/l/invite/abc"));
+        assert_eq!(native_url("https://teams.microsoft.com/v2/"), None);
+    }
+
+    #[test]
+    fn rewrites_spotify_links() {
+        assert_eq!(native_url("https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC"), rewritten("Spotify", "spotify:track:4uLU6hMCjMI75M1A2tKUQC"));
+        assert_eq!(
+            native_url("https://open.spotify.com/intl-de/album/1DFixLWuPkv3KT3TnV35m3?si=x"),
+            rewritten("Spotify", "spotify:album:1DFixLWuPkv3KT3TnV35m3"),
+        );
+        assert_eq!(native_url("https://open.spotify.com/"), None);
+    }
+
+    #[test]
+    fn rewrites_slack_channels() {
+        assert_eq!(native_url("https://app.slack.com/client/T0123/C0456/thread/C0456-1"), rewritten("Slack", "slack://channel?team=T0123&id=C0456"));
+        assert_eq!(native_url("https://app.slack.com/client/T0123"), None);
+    }
+
+    #[test]
+    fn rewrites_figma_files() {
+        assert_eq!(native_url("https://www.figma.com/file/abc/Design?node-id=1"), rewritten("Figma", "figma://file/abc/Design?node-id=1"));
+        assert_eq!(native_url("https://figma.com/proto/abc"), rewritten("Figma", "figma://proto/abc"));
+        assert_eq!(native_url("https://www.figma.com/pricing"), None);
+    }
+
+    #[test]
+    fn ignores_other_hosts_and_schemes() {
+        for url in [
+            "https://zoom.us.evil.com/j/123",
+            "https://evilzoom.us/j/123",
+            "https://teams.microsoft.com.evil.com/l/meetup-join/1",
+            "https://open.spotify.com.evil.com/track/1",
+            "https://notslack.com/client/T0123/C0456",
+            "https://app.slack.com.evil.com/client/T0123/C0456",
+            "https://notfigma.com/file/abc",
+            "ftp://zoom.us/j/123",
+            "https://example.com/j/123",
+            "not a url",
+        ] {
+            assert_eq!(native_url(url), None, "{url}");
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use gio::prelude::*;
+use gio::glib;
+use gio::Settings;
+
+
+const SECONDS_IN_DAY: i64 = 24 * 60 * 60;
+
+/// Size of the history file over which recording an entry drops the entries over the retention limits,
+/// below it they are only dropped when the history is loaded.
+const PRUNE_SIZE: u64 = 256 * 1024;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DispatchOrigin {
+    /// The target was chosen in the picker.
+    Manual,
+    /// The target was picked by a rule without showing the picker.
+    Rule,
+    /// The countdown ran out without the user choosing a target.
+    Countdown,
+}
+
+
+impl DispatchOrigin {
+    fn id(&self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Rule => "rule",
+            Self::Countdown => "countdown",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "manual" => Some(Self::Manual),
+            "rule" => Some(Self::Rule),
+            "countdown" => Some(Self::Countdown),
+            _ => None,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Manual => "chosen manually",
+            Self::Rule => "opened by a rule",
+            Self::Countdown => "opened after the countdown",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Unix time of the dispatch.
+    pub timestamp: i64,
+    pub url: String,
+    pub target_id: String,
+    pub target_name: String,
+    pub origin: DispatchOrigin,
+}
+
+
+impl HistoryEntry {
+    pub fn new(url: String, target_id: String, target_name: String, origin: DispatchOrigin) -> Self {
+        HistoryEntry {
+            timestamp: now(),
+            url,
+            target_id,
+            target_name,
+            origin,
+        }
+    }
+
+    pub fn formatted_time(&self) -> String {
+        glib::DateTime::from_unix_local(self.timestamp)
+            .and_then(|time| time.format("%Y-%m-%d %H:%M"))
+            .map(String::from)
+            .unwrap_or_default()
+    }
+
+    /// Entries are stored one per line with tab separated fields, tabs and line breaks can not
+    /// appear in urls and are dropped from names.
+    fn to_line(&self) -> String {
+        let clean = |field: &str| field.replace(['\t', '\n', '\r'], " ");
+        format!(
+            "{}\t{}\t{}\t{}\t{}\n",
+            self.timestamp,
+            self.origin.id(),
+            clean(&self.target_id),
+            clean(&self.target_name),
+            clean(&self.url),
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(5, '\t');
+        Some(HistoryEntry {
+            timestamp: fields.next()?.parse().ok()?,
+            origin: DispatchOrigin::from_id(fields.next()?)?,
+            target_id: fields.next()?.to_string(),
+            target_name: fields.next()?.to_string(),
+            url: fields.next()?.to_string(),
+        })
+    }
+}
+
+
+fn now() -> i64 {
+    glib::DateTime::now_local().map(|time| time.to_unix()).unwrap_or_default()
+}
+
+
+fn history_path() -> PathBuf {
+    glib::user_state_dir().join("browdi").join("history.tsv")
+}
+
+
+fn read() -> Vec<HistoryEntry> {
+    let Ok(file) = File::open(history_path()) else { return Vec::new() };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| HistoryEntry::from_line(&line))
+        .collect()
+}
+
+
+/// Reads all entries within the retention limits, oldest first, and drops the others from the file.
+pub fn load(settings: &Settings) -> Vec<HistoryEntry> {
+    let _ = prune(settings);
+    read()
+}
+
+
+/// Appends `entry` to the history unless history is disabled, entries over the retention limits
+/// are dropped once the file grows large.
+pub fn record(settings: &Settings, entry: HistoryEntry) -> io::Result<()> {
+    if !settings.boolean("history-enabled") {
+        return Ok(());
+    }
+    let path = history_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)?
+        .write_all(entry.to_line().as_bytes())?;
+    if fs::metadata(&path)?.len() > PRUNE_SIZE {
+        prune(settings)?;
+    }
+    Ok(())
+}
+
+
+/// Entries of `entries` not older than `max_days` before `now` and only the last `max_entries`
+/// of them, zero means no limit.
+fn retained(entries: &[HistoryEntry], max_entries: usize, max_days: i64, now: i64) -> Vec<&HistoryEntry> {
+    let oldest_allowed = now - max_days * SECONDS_IN_DAY;
+    let recent: Vec<&HistoryEntry> = entries.iter()
+        .filter(|entry| max_days == 0 || entry.timestamp >= oldest_allowed)
+        .collect();
+    let skipped = match max_entries {
+        0 => 0,
+        max_entries => recent.len().saturating_sub(max_entries),
+    };
+    recent.into_iter().skip(skipped).collect()
+}
+
+
+/// Drops entries older than `history-max-days` and all but the last `history-max-entries` from the file.
+fn prune(settings: &Settings) -> io::Result<()> {
+    let max_entries = settings.uint("history-max-entries") as usize;
+    let max_days = settings.uint("history-max-days") as i64;
+    let entries = read();
+    let kept = retained(&entries, max_entries, max_days, now());
+    if kept.len() == entries.len() {
+        return Ok(());
+    }
+    let path = history_path();
+    let tmp_path = path.with_extension("tsv.tmp");
+    let mut tmp = File::create(&tmp_path)?;
+    for entry in kept {
+        tmp.write_all(entry.to_line().as_bytes())?;
+    }
+    fs::rename(tmp_path, path)
+}
+
+
+pub fn clear() -> io::Result<()> {
+    match fs::remove_file(history_path()) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(timestamp: i64, url: &str) -> HistoryEntry {
+        HistoryEntry {
+            timestamp,
+            url: url.to_string(),
+            target_id: String::from("firefox.desktop"),
+            target_name: String::from("Firefox"),
+            origin: DispatchOrigin::Rule,
+        }
+    }
+
+    #[test]
+    fn reads_written_lines() {
+        let written = entry(1_700_000_000, "https://example.com/a?b=c#d");
+        assert_eq!(written.to_line(), "1700000000\trule\tfirefox.desktop\tFirefox\thttps://example.com/a?b=c#d\n");
+        assert_eq!(HistoryEntry::from_line(written.to_line().trim_end_matches('\n')), Some(written));
+    }
+
+    #[test]
+    fn drops_separators_from_fields() {
+        let written = HistoryEntry {
+            target_name: String::from("Firefox\tNightly\n"),
+            ..entry(1_700_000_000, "https://example.com/\ta\r\nb")
+        };
+        let line = written.to_line();
+        assert_eq!(line.matches('\t').count(), 4);
+        assert_eq!(line.matches('\n').count(), 1);
+        let read = HistoryEntry::from_line(line.trim_end_matches('\n')).unwrap();
+        assert_eq!(read.target_name, "Firefox Nightly ");
+        assert_eq!(read.url, "https://example.com/ a  b");
+    }
+
+    #[test]
+    fn skips_bad_lines() {
+        assert_eq!(HistoryEntry::from_line(""), None);
+        assert_eq!(HistoryEntry::from_line("1700000000\trule\tfirefox.desktop\tFirefox"), None);
+        assert_eq!(HistoryEntry::from_line("yesterday\trule\tfirefox.desktop\tFirefox\thttps://example.com/"), None);
+        assert_eq!(HistoryEntry::from_line("1700000000\tmagic\tfirefox.desktop\tFirefox\thttps://example.com/"), None);
+    }
+
+    #[test]
+    fn retains_recent_entries() {
+        let now = 100 * SECONDS_IN_DAY;
+        let entries: Vec<HistoryEntry> = [80, 89, 90, 95, 99].iter()
+            .map(|day| entry(day * SECONDS_IN_DAY, &format!("https://example.com/{day}")))
+            .collect();
+        let last = |count: usize| entries[entries.len() - count..].iter().collect::<Vec<_>>();
+        assert_eq!(retained(&entries, 0, 0, now), last(5));
+        assert_eq!(retained(&entries, 0, 10, now), last(3));
+        assert_eq!(retained(&entries, 2, 0, now), last(2));
+        assert_eq!(retained(&entries, 2, 30, now), last(2));
+        assert_eq!(retained(&entries, 10, 10, now), last(3));
+        assert_eq!(retained(&entries, 0, 1, now), last(1));
+        assert!(retained(&[], 5, 5, now).is_empty());
+    }
+}
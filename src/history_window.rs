@@ -0,0 +1,205 @@
+use gio::{AppInfo, File, Settings};
+use gio::prelude::AppInfoExt;
+use gtk::glib::{self, clone};
+use relm4::{adw, gtk, ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent};
+use relm4::adw::prelude::*;
+
+use crate::history::{self, HistoryEntry};
+use crate::rules;
+
+
+pub struct HistoryInit {
+    pub browsers: Vec<AppInfo>,
+    pub settings: Settings,
+}
+
+
+pub struct HistoryModel {
+    window: adw::Window,
+    browsers: Vec<AppInfo>,
+    settings: Settings,
+    entries: Vec<HistoryEntry>,
+    query: String,
+    list: gtk::ListBox,
+}
+
+
+#[derive(Debug)]
+pub enum HistoryInputMessage {
+    Show,
+    SearchChanged(String),
+    Reopen(usize, usize),
+    MakeRule(usize),
+    Clear,
+}
+
+
+#[derive(Debug)]
+pub enum HistoryOutputMessage {
+    /// Open the url in the browser with the given id.
+    Reopen(String, String),
+}
+
+
+impl HistoryModel {
+    fn target_browser(&self, entry: &HistoryEntry) -> Option<&AppInfo> {
+        self.browsers.iter().find(|browser| browser.id().is_some_and(|id| id == entry.target_id))
+    }
+
+    fn entry_menu(&self, number: usize, entry: &HistoryEntry, sender: &ComponentSender<Self>) -> gtk::MenuButton {
+        let popover = gtk::Popover::new();
+        let actions = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        for (browser_number, browser) in self.browsers.iter().enumerate() {
+            let button = gtk::Button::builder()
+                .label(format!("Open in {}", browser.name()))
+                .css_classes(vec!["flat"])
+                .build();
+            button.connect_clicked(clone!(@strong sender, @weak popover => move |_| {
+                popover.popdown();
+                sender.input(HistoryInputMessage::Reopen(number, browser_number));
+            }));
+            actions.append(&button);
+        }
+        if let Some(browser) = self.target_browser(entry).filter(|_| rules::is_web_url(&File::for_uri(&entry.url))) {
+            actions.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+            let button = gtk::Button::builder()
+                .label(format!("Always open {} in {}", rules::domain_of(&entry.url), browser.name()))
+                .css_classes(vec!["flat"])
+                .build();
+            button.connect_clicked(clone!(@strong sender, @weak popover => move |_| {
+                popover.popdown();
+                sender.input(HistoryInputMessage::MakeRule(number));
+            }));
+            actions.append(&button);
+        }
+        popover.set_child(Some(&actions));
+        gtk::MenuButton::builder()
+            .icon_name("view-more-symbolic")
+            .valign(gtk::Align::Center)
+            .css_classes(vec!["flat"])
+            .popover(&popover)
+            .build()
+    }
+
+    fn reload_list(&self, sender: &ComponentSender<Self>) {
+        while let Some(row) = self.list.first_child() {
+            self.list.remove(&row);
+        }
+        let query = self.query.to_lowercase();
+        for (number, entry) in self.entries.iter().enumerate().rev() {
+            if !entry.url.to_lowercase().contains(&query) && !entry.target_name.to_lowercase().contains(&query) {
+                continue;
+            }
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&entry.url))
+                .subtitle(glib::markup_escape_text(&format!(
+                    "{} · {}, {}", entry.formatted_time(), entry.target_name, entry.origin.description(),
+                )))
+                .build();
+            row.add_suffix(&self.entry_menu(number, entry, sender));
+            self.list.append(&row);
+        }
+    }
+}
+
+
+#[relm4::component(pub)]
+impl SimpleComponent for HistoryModel {
+    type Input = HistoryInputMessage;
+    type Output = HistoryOutputMessage;
+    type Init = HistoryInit;
+
+    view! {
+        adw::Window {
+            set_title: Some("History"),
+            set_default_width: 640,
+            set_default_height: 480,
+            set_hide_on_close: true,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+
+                adw::HeaderBar {
+                    pack_end = &gtk::Button {
+                        set_label: "Clear history",
+                        add_css_class: "destructive-action",
+                        connect_clicked => HistoryInputMessage::Clear,
+                    },
+                },
+
+                gtk::SearchEntry {
+                    set_placeholder_text: Some("Search history"),
+                    set_margin_all: 5,
+
+                    connect_search_changed[sender] => move |entry| {
+                        sender.input(HistoryInputMessage::SearchChanged(entry.text().into()));
+                    },
+                },
+
+                gtk::ScrolledWindow {
+                    set_vexpand: true,
+                    set_hscrollbar_policy: gtk::PolicyType::Never,
+
+                    #[local_ref]
+                    list -> gtk::ListBox {
+                        set_margin_all: 5,
+                        set_valign: gtk::Align::Start,
+                        set_selection_mode: gtk::SelectionMode::None,
+                        add_css_class: "boxed-list",
+                        set_placeholder: Some(&gtk::Label::new(Some("History is empty"))),
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        window: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let list = gtk::ListBox::new();
+        let model = HistoryModel {
+            window: window.clone(),
+            browsers: init.browsers,
+            settings: init.settings,
+            entries: Vec::new(),
+            query: String::new(),
+            list: list.clone(),
+        };
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            HistoryInputMessage::Show => {
+                self.entries = history::load(&self.settings);
+                self.reload_list(&sender);
+                self.window.present();
+            }
+            HistoryInputMessage::SearchChanged(query) => {
+                self.query = query;
+                self.reload_list(&sender);
+            }
+            HistoryInputMessage::Reopen(number, browser_number) => {
+                let browser_id = self.browsers.get(browser_number).and_then(|browser| browser.id());
+                if let (Some(entry), Some(browser_id)) = (self.entries.get(number), browser_id) {
+                    sender.output(HistoryOutputMessage::Reopen(entry.url.clone(), browser_id.into())).unwrap();
+                }
+            }
+            HistoryInputMessage::MakeRule(number) => {
+                if let Some(entry) = self.entries.get(number) {
+                    if let Some(browser) = self.target_browser(entry) {
+                        rules::remember(&self.settings, browser, rules::domain_of(&entry.url));
+                    }
+                }
+            }
+            HistoryInputMessage::Clear => {
+                let _ = history::clear();
+                self.entries.clear();
+                self.reload_list(&sender);
+            }
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use std::collections::{HashMap, HashSet};
+
+use gio::prelude::*;
+use gio::{AppInfo, Settings};
+use itertools::Itertools;
+use relm4::gtk::gdk;
+use relm4::gtk::prelude::DisplayExtManual;
+
+
+/// Keys taken by the built-in shortcuts, they can not be assigned to a browser.
+pub const RESERVED_HOTKEYS: [char; 6] = ['Q', 'S', 'H', 'D', 'M', 'E'];
+
+/// Keys tried in order when none of the letters of a browser name is free. Digits are left out
+/// as they would shadow opening browsers by their position, they can only be assigned by hand.
+const FALLBACK_HOTKEYS: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HotkeyConflict {
+    /// Only latin letters and digits can be used as hotkeys.
+    Invalid,
+    /// The key is used by one of the built-in shortcuts.
+    Reserved,
+    /// The key is already assigned to the browser with the given id.
+    Browser(String),
+}
+
+
+/// Converts a typed character into the form hotkeys are stored and displayed in.
+pub fn normalize(key: char) -> Option<char> {
+    key.is_ascii_alphanumeric().then(|| key.to_ascii_uppercase())
+}
+
+
+/// Returns the latin character of a pressed key.
+///
+/// When a non-latin layout (cyrillic, greek, hebrew, ...) is active the key does not produce a latin
+/// character, so its hardware keycode is translated through the first layout group that does.
+pub fn latin_key(key: gdk::Key, keycode: u32) -> Option<char> {
+    let is_latin = |key: &char| key.is_ascii_graphic();
+    if let Some(key) = key.to_unicode().filter(is_latin) {
+        return Some(key);
+    }
+    // keys that do not produce a character at all (arrows, modifiers, ...) have nothing to translate
+    key.to_unicode()?;
+    unshifted_key(keycode, is_latin)
+}
+
+
+/// Returns the latin letter or digit the key with `keycode` produces without modifiers, e.g. `1`
+/// for the key that produces `!` with Shift.
+pub fn base_key(keycode: u32) -> Option<char> {
+    unshifted_key(keycode, char::is_ascii_alphanumeric).and_then(normalize)
+}
+
+
+/// First character accepted by `filter` on the level without modifiers of the layout groups of `keycode`.
+fn unshifted_key(keycode: u32, filter: impl Fn(&char) -> bool) -> Option<char> {
+    gdk::Display::default()?
+        .map_keycode(keycode)?
+        .into_iter()
+        .filter(|(keymap_key, _)| keymap_key.level() == 0)
+        .sorted_by_key(|(keymap_key, _)| keymap_key.group())
+        .find_map(|(_, key)| key.to_unicode().filter(&filter))
+}
+
+
+/// Checks if `key` can be assigned to the browser `browser_id` given the hotkeys of the other browsers.
+pub fn check(key: char, browser_id: &str, hotkeys: &HashMap<String, Option<char>>) -> Result<char, HotkeyConflict> {
+    let key = normalize(key).ok_or(HotkeyConflict::Invalid)?;
+    if RESERVED_HOTKEYS.contains(&key) {
+        return Err(HotkeyConflict::Reserved);
+    }
+    match hotkeys.iter().find(|(id, hotkey)| id.as_str() != browser_id && **hotkey == Some(key)) {
+        Some((id, _)) => Err(HotkeyConflict::Browser(id.clone())),
+        None => Ok(key),
+    }
+}
+
+
+/// Resolves hotkeys for `browsers`.
+///
+/// Hotkeys stored in settings are kept as long as they are valid and unique, an empty string stands
+/// for a browser which hotkey was removed by the user. Every other browser gets the first free letter
+/// of its name, then any other free letter.
+pub fn resolve(browsers: &[AppInfo], stored: &HashMap<String, String>) -> HashMap<String, Option<char>> {
+    let browsers: Vec<(String, String)> = browsers.iter()
+        .filter_map(|browser| Some((browser.id()?.into(), browser.name().into())))
+        .collect();
+    resolve_names(&browsers, stored)
+}
+
+
+/// [`resolve`] for browsers given as ids and names.
+fn resolve_names(browsers: &[(String, String)], stored: &HashMap<String, String>) -> HashMap<String, Option<char>> {
+    let mut taken: HashSet<char> = RESERVED_HOTKEYS.into_iter().collect();
+    let mut hotkeys = HashMap::new();
+    let mut unassigned = Vec::new();
+
+    for (id, name) in browsers.iter() {
+        match stored.get(id).map(|key| key.chars().next()) {
+            Some(None) => {
+                hotkeys.insert(id.clone(), None);
+            },
+            Some(Some(key)) => match normalize(key).filter(|key| !taken.contains(key)) {
+                Some(key) => {
+                    taken.insert(key);
+                    hotkeys.insert(id.clone(), Some(key));
+                },
+                None => unassigned.push((id, name)),
+            },
+            None => unassigned.push((id, name)),
+        }
+    }
+
+    for (id, name) in unassigned {
+        let key = name.chars()
+            .chain(FALLBACK_HOTKEYS.chars())
+            .filter(char::is_ascii_alphabetic)
+            .filter_map(normalize)
+            .find(|key| !taken.contains(key));
+        if let Some(key) = key {
+            taken.insert(key);
+        }
+        hotkeys.insert(id.clone(), key);
+    }
+    hotkeys
+}
+
+
+/// Browsers with a stored hotkey that became a built-in shortcut, like `E` editing the url, and
+/// their old hotkeys. [`load`] gives them other keys.
+pub fn stored_reserved(settings: &Settings, browsers: &[AppInfo]) -> Vec<(AppInfo, char)> {
+    let stored: HashMap<String, String> = settings.get("browser-hotkeys");
+    browsers.iter()
+        .filter_map(|browser| {
+            let key = stored.get(browser.id()?.as_str())?.chars().next().and_then(normalize)?;
+            RESERVED_HOTKEYS.contains(&key).then(|| (browser.clone(), key))
+        })
+        .collect()
+}
+
+
+/// Loads hotkeys for `browsers` and persists the ones assigned by default, so they stay the same
+/// when other browsers are installed or removed.
+pub fn load(settings: &Settings, browsers: &[AppInfo]) -> HashMap<String, Option<char>> {
+    let mut stored: HashMap<String, String> = settings.get("browser-hotkeys");
+    let hotkeys = resolve(browsers, &stored);
+    let mut changed = false;
+    for (id, hotkey) in hotkeys.iter() {
+        let value = hotkey.map(String::from).unwrap_or_default();
+        if stored.get(id) != Some(&value) {
+            stored.insert(id.clone(), value);
+            changed = true;
+        }
+    }
+    if changed {
+        let _ = settings.set("browser-hotkeys", stored);
+    }
+    hotkeys
+}
+
+
+/// Stores `hotkey` for the browser `browser_id`, `None` removes the hotkey.
+pub fn save(settings: &Settings, browser_id: &str, hotkey: Option<char>) {
+    let mut stored: HashMap<String, String> = settings.get("browser-hotkeys");
+    stored.insert(browser_id.to_string(), hotkey.map(String::from).unwrap_or_default());
+    let _ = settings.set("browser-hotkeys", stored);
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LaunchAction {
+    #[default]
+    Open,
+    OpenPrivate,
+    OpenAndRemember,
+    OpenAndKeep,
+    /// Open in a window without browser interface, see [`crate::launch::BrowserFamily::app_args`].
+    OpenAsApp,
+    /// Open with a temporary profile deleted after the browser exits.
+    OpenThrowaway,
+    /// Mark the browser to open the url in all marked browsers at once.
+    Mark,
+}
+
+
+impl LaunchAction {
+    pub const ALL: [LaunchAction; 7] = [
+        Self::Open, Self::OpenPrivate, Self::OpenAndRemember, Self::OpenAndKeep, Self::OpenAsApp, Self::OpenThrowaway,
+        Self::Mark,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::OpenPrivate => "private",
+            Self::OpenAndRemember => "remember",
+            Self::OpenAndKeep => "keep-open",
+            Self::OpenAsApp => "app",
+            Self::OpenThrowaway => "throwaway",
+            Self::Mark => "mark",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Open => "Open",
+            Self::OpenPrivate => "Open in a private window",
+            Self::OpenAndRemember => "Open and remember for the domain",
+            Self::OpenAndKeep => "Open and keep BrowDi open",
+            Self::OpenAsApp => "Open as an app",
+            Self::OpenThrowaway => "Open in a throwaway session",
+            Self::Mark => "Mark to open in several browsers",
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LaunchModifier {
+    Shift,
+    Ctrl,
+    Alt,
+    MiddleClick,
+}
+
+
+impl LaunchModifier {
+    pub const ALL: [LaunchModifier; 4] = [Self::Shift, Self::Ctrl, Self::Alt, Self::MiddleClick];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Shift => "shift",
+            Self::Ctrl => "ctrl",
+            Self::Alt => "alt",
+            Self::MiddleClick => "middle-click",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Shift => "Shift",
+            Self::Ctrl => "Ctrl",
+            Self::Alt => "Alt",
+            Self::MiddleClick => "Middle click",
+        }
+    }
+}
+
+
+pub fn load_modifier_actions(settings: &Settings) -> HashMap<LaunchModifier, LaunchAction> {
+    let stored: HashMap<String, String> = settings.get("modifier-actions");
+    LaunchModifier::ALL.into_iter()
+        .map(|modifier| {
+            let action = stored.get(modifier.id()).and_then(|id| LaunchAction::from_id(id)).unwrap_or_default();
+            (modifier, action)
+        })
+        .collect()
+}
+
+
+pub fn save_modifier_action(settings: &Settings, modifier: LaunchModifier, action: LaunchAction) {
+    let mut stored: HashMap<String, String> = settings.get("modifier-actions");
+    stored.insert(modifier.id().to_string(), action.id().to_string());
+    let _ = settings.set("modifier-actions", stored);
+}
+
+
+/// Picks the action for a key press or a click made while holding modifiers in `state`,
+/// Ctrl wins over Shift and Shift wins over Alt when several of them are held. Ctrl and Shift
+/// together always mark, so marking does not need a modifier of its own.
+pub fn action_for_state(actions: &HashMap<LaunchModifier, LaunchAction>, state: gdk::ModifierType) -> LaunchAction {
+    if state.contains(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::SHIFT_MASK) {
+        return LaunchAction::Mark;
+    }
+    [
+        (gdk::ModifierType::CONTROL_MASK, LaunchModifier::Ctrl),
+        (gdk::ModifierType::SHIFT_MASK, LaunchModifier::Shift),
+        (gdk::ModifierType::ALT_MASK, LaunchModifier::Alt),
+    ].into_iter()
+        .find(|(mask, _)| state.contains(*mask))
+        .and_then(|(_, modifier)| actions.get(&modifier).copied())
+        .unwrap_or_default()
+}
+
+
+/// Describes the configured modifier actions, one line per modifier that does something else than opening,
+/// followed by the fixed marking chord.
+pub fn modifier_actions_help(actions: &HashMap<LaunchModifier, LaunchAction>) -> String {
+    LaunchModifier::ALL.into_iter()
+        .filter_map(|modifier| {
+            let action = actions.get(&modifier).copied().unwrap_or_default();
+            let key = match modifier {
+                LaunchModifier::MiddleClick => modifier.label().to_string(),
+                _ => format!("{}+key", modifier.label()),
+            };
+            (action != LaunchAction::Open).then(|| format!("{}: {}", key, action.description().to_lowercase()))
+        })
+        .chain(std::iter::once(format!("Ctrl+Shift+key: {}", LaunchAction::Mark.description().to_lowercase())))
+        .join("\n")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn browsers(names: &[(&str, &str)]) -> Vec<(String, String)> {
+        names.iter().map(|(id, name)| (id.to_string(), name.to_string())).collect()
+    }
+
+    fn stored(keys: &[(&str, &str)]) -> HashMap<String, String> {
+        keys.iter().map(|(id, key)| (id.to_string(), key.to_string())).collect()
+    }
+
+    #[test]
+    fn keeps_stored_hotkeys() {
+        let hotkeys = resolve_names(
+            &browsers(&[("firefox", "Firefox"), ("chromium", "Chromium"), ("epiphany", "Web")]),
+            &stored(&[("firefox", "x"), ("chromium", "7"), ("epiphany", "")]),
+        );
+        assert_eq!(hotkeys["firefox"], Some('X'));
+        assert_eq!(hotkeys["chromium"], Some('7'));
+        assert_eq!(hotkeys["epiphany"], None);
+    }
+
+    #[test]
+    fn reassigns_reserved_and_duplicate_hotkeys() {
+        let hotkeys = resolve_names(
+            &browsers(&[("firefox", "Firefox"), ("edge", "Edge"), ("falkon", "Falkon")]),
+            &stored(&[("firefox", "F"), ("edge", "E"), ("falkon", "f")]),
+        );
+        assert_eq!(hotkeys["firefox"], Some('F'));
+        // E is reserved, so is D, the first free letter of the name is G
+        assert_eq!(hotkeys["edge"], Some('G'));
+        assert_eq!(hotkeys["falkon"], Some('A'));
+    }
+
+    #[test]
+    fn assigns_letters_of_names_then_other_letters() {
+        let hotkeys = resolve_names(
+            &browsers(&[("a", "Brave"), ("b", "Brave Beta"), ("c", "2345"), ("d", "Qh")]),
+            &HashMap::new(),
+        );
+        assert_eq!(hotkeys["a"], Some('B'));
+        assert_eq!(hotkeys["b"], Some('R'));
+        // digits are never assigned automatically
+        assert_eq!(hotkeys["c"], Some('A'));
+        assert_eq!(hotkeys["d"], Some('C'));
+    }
+
+    #[test]
+    fn runs_out_of_letters() {
+        let names: Vec<(String, String)> = (0..30).map(|number| (number.to_string(), String::from("Browser"))).collect();
+        let hotkeys = resolve_names(&names, &HashMap::new());
+        assert_eq!(hotkeys.values().filter(|key| key.is_some()).count(), 26 - RESERVED_HOTKEYS.len());
+        assert!(hotkeys.values().flatten().all(char::is_ascii_uppercase));
+    }
+
+    #[test]
+    fn checks_hotkeys() {
+        let hotkeys = HashMap::from([(String::from("firefox"), Some('F')), (String::from("chromium"), None)]);
+        assert_eq!(check('c', "chromium", &hotkeys), Ok('C'));
+        assert_eq!(check('f', "firefox", &hotkeys), Ok('F'));
+        assert_eq!(check('F', "chromium", &hotkeys), Err(HotkeyConflict::Browser(String::from("firefox"))));
+        assert_eq!(check('q', "chromium", &hotkeys), Err(HotkeyConflict::Reserved));
+        assert_eq!(check('é', "chromium", &hotkeys), Err(HotkeyConflict::Invalid));
+        assert_eq!(check('3', "chromium", &hotkeys), Ok('3'));
+    }
+
+    #[test]
+    fn marks_with_ctrl_and_shift() {
+        let actions = HashMap::from([
+            (LaunchModifier::Ctrl, LaunchAction::OpenAndRemember),
+            (LaunchModifier::Shift, LaunchAction::OpenPrivate),
+        ]);
+        let (ctrl, shift, alt) = (gdk::ModifierType::CONTROL_MASK, gdk::ModifierType::SHIFT_MASK, gdk::ModifierType::ALT_MASK);
+        assert_eq!(action_for_state(&actions, gdk::ModifierType::empty()), LaunchAction::Open);
+        assert_eq!(action_for_state(&actions, ctrl), LaunchAction::OpenAndRemember);
+        assert_eq!(action_for_state(&actions, shift | alt), LaunchAction::OpenPrivate);
+        assert_eq!(action_for_state(&actions, alt), LaunchAction::Open);
+        assert_eq!(action_for_state(&actions, ctrl | shift), LaunchAction::Mark);
+        assert_eq!(action_for_state(&HashMap::new(), ctrl | shift | alt), LaunchAction::Mark);
+        assert!(modifier_actions_help(&HashMap::new()).starts_with("Ctrl+Shift+key: mark"));
+    }
+}
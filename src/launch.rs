@@ -0,0 +1,573 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+
+use gio::prelude::*;
+use gio::{glib, AppInfo, File, Settings};
+
+use crate::containers;
+
+
+/// Sandbox wrappers offered in the preferences as names and commands, `$HOME` is replaced
+/// with the home directory when a browser is launched. The bubblewrap one keeps the X11 socket
+/// and cookie the temporary directories would hide, and the profiles of throwaway sessions.
+pub const WRAPPER_PRESETS: [(&str, &str); 3] = [
+    ("Firejail", "firejail"),
+    ("Firejail with a temporary home", "firejail --private"),
+    (
+        "Bubblewrap with a temporary home",
+        "bwrap --ro-bind / / --dev /dev --proc /proc --tmpfs /tmp --ro-bind-try /tmp/.X11-unix /tmp/.X11-unix \
+         --tmpfs $HOME --ro-bind-try $HOME/.Xauthority $HOME/.Xauthority \
+         --bind-try $HOME/.cache/browdi/throwaway $HOME/.cache/browdi/throwaway --unshare-pid --new-session",
+    ),
+];
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrowserFamily {
+    Firefox,
+    Chromium,
+    Epiphany,
+}
+
+
+impl BrowserFamily {
+    /// Guesses the family of a browser from its desktop file id and command line.
+    pub fn of(app: &AppInfo) -> Option<Self> {
+        let haystack = format!(
+            "{} {}",
+            app.id().unwrap_or_default(),
+            app.commandline().unwrap_or_default().to_string_lossy(),
+        ).to_lowercase();
+        let contains_any = |needles: &[&str]| needles.iter().any(|needle| haystack.contains(needle));
+        if contains_any(&["firefox", "librewolf", "waterfox", "floorp", "zen-browser"]) {
+            Some(Self::Firefox)
+        } else if contains_any(&["chrom", "brave", "vivaldi", "msedge", "microsoft-edge", "opera", "yandex"]) {
+            Some(Self::Chromium)
+        } else if contains_any(&["epiphany"]) {
+            Some(Self::Epiphany)
+        } else {
+            None
+        }
+    }
+
+    pub fn private_flag(&self) -> &'static str {
+        match self {
+            Self::Firefox => "--private-window",
+            Self::Chromium => "--incognito",
+            Self::Epiphany => "--incognito-mode",
+        }
+    }
+
+    /// Arguments opening `%u` in a window without tabs and toolbars, only Chromium can do it.
+    /// Firefox has just a fullscreen kiosk mode without a way out and Epiphany can only open web
+    /// apps installed in it. A dedicated `profile` keeps cookies of the app separate.
+    pub fn app_args(&self, profile: Option<&PathBuf>) -> Option<Vec<String>> {
+        let mut args = Vec::new();
+        match self {
+            Self::Chromium => {
+                if let Some(profile) = profile {
+                    args.push(format!("--user-data-dir={}", profile.display()));
+                }
+                args.push(String::from("--app=%u"));
+            },
+            Self::Firefox | Self::Epiphany => return None,
+        }
+        Some(args)
+    }
+
+    /// Arguments starting a separate instance of the browser with the `profile` directory.
+    pub fn profile_args(&self, profile: &Path) -> Option<Vec<String>> {
+        match self {
+            Self::Chromium => Some(vec![
+                format!("--user-data-dir={}", profile.display()),
+                String::from("--no-first-run"),
+                String::from("--no-default-browser-check"),
+            ]),
+            Self::Firefox => Some(vec![
+                String::from("--no-remote"),
+                String::from("--profile"),
+                profile.display().to_string(),
+            ]),
+            Self::Epiphany => None,
+        }
+    }
+}
+
+
+/// A browser started with a temporary profile, the profile is deleted after the browser exits.
+#[derive(Debug)]
+pub struct ThrowawaySession {
+    pub pid: glib::Pid,
+    pub profile: PathBuf,
+}
+
+
+impl ThrowawaySession {
+    /// Deletes the profile once the browser exits and then calls `ended`.
+    pub fn watch(self, ended: impl Fn() + 'static) {
+        glib::child_watch_add_local(self.pid, move |_, _| {
+            let _ = fs::remove_dir_all(&self.profile);
+            ended();
+        });
+    }
+}
+
+
+/// Directory with the temporary profiles of throwaway sessions.
+fn throwaway_root() -> PathBuf {
+    glib::user_cache_dir().join("browdi").join("throwaway")
+}
+
+
+/// Creates a new private directory for a temporary profile.
+fn throwaway_profile_dir() -> io::Result<PathBuf> {
+    let root = throwaway_root();
+    fs::create_dir_all(&root)?;
+    let dir = root.join(format!("{}-{}", std::process::id(), glib::real_time()));
+    DirBuilder::new().mode(0o700).create(&dir)?;
+    Ok(dir)
+}
+
+
+/// Deletes temporary profiles left behind when BrowDi was not running as their browser exited,
+/// profiles still on the command line of a running process are kept.
+pub fn remove_stale_profiles() {
+    let Ok(entries) = fs::read_dir(throwaway_root()) else { return };
+    let command_lines: Vec<String> = fs::read_dir("/proc").into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| fs::read(entry.path().join("cmdline")).ok())
+        .map(|cmdline| String::from_utf8_lossy(&cmdline).into_owned())
+        .collect();
+    for dir in entries.flatten().map(|entry| entry.path()) {
+        let path = dir.to_string_lossy();
+        if !command_lines.iter().any(|cmdline| cmdline.contains(&*path)) {
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaunchOptions {
+    pub private: bool,
+    pub app_mode: bool,
+    pub throwaway: bool,
+    /// Apps get a profile per browser and domain when opened in app mode.
+    pub app_profile: bool,
+    /// Arguments added to the command line of the browser, `%u` and `%U` in them are replaced with urls.
+    pub args: Vec<String>,
+    /// Environment variables set for the browser.
+    pub env: HashMap<String, String>,
+    /// Name of a Firefox container to open urls in, empty to open them as usual.
+    pub container: String,
+    /// Command the browser is started with, e.g. a sandbox like `firejail`.
+    pub wrapper: Vec<String>,
+    /// Name of a webmail `mailto:` links are composed in, empty for the default one.
+    pub webmail: String,
+}
+
+
+impl LaunchOptions {
+    /// Options configured for `app` in the preferences.
+    pub fn load(settings: &Settings, app: &AppInfo) -> Self {
+        let id = app.id().map(String::from).unwrap_or_default();
+        let mut args: HashMap<String, Vec<String>> = settings.get("browser-arguments");
+        let mut env: HashMap<String, HashMap<String, String>> = settings.get("browser-environment");
+        let mut wrappers: HashMap<String, Vec<String>> = settings.get("browser-wrappers");
+        LaunchOptions {
+            private: false,
+            app_mode: false,
+            throwaway: false,
+            app_profile: settings.boolean("app-mode-profiles"),
+            args: args.remove(&id).unwrap_or_default(),
+            env: env.remove(&id).unwrap_or_default(),
+            container: String::new(),
+            wrapper: wrappers.remove(&id).unwrap_or_default(),
+            webmail: String::new(),
+        }
+    }
+
+    pub fn save(&self, settings: &Settings, app: &AppInfo) {
+        let Some(id) = app.id().map(String::from) else { return };
+        let mut args: HashMap<String, Vec<String>> = settings.get("browser-arguments");
+        let mut env: HashMap<String, HashMap<String, String>> = settings.get("browser-environment");
+        let mut wrappers: HashMap<String, Vec<String>> = settings.get("browser-wrappers");
+        args.insert(id.clone(), self.args.clone());
+        env.insert(id.clone(), self.env.clone());
+        wrappers.insert(id.clone(), self.wrapper.clone());
+        args.retain(|_, args| !args.is_empty());
+        env.retain(|_, env| !env.is_empty());
+        wrappers.retain(|_, wrapper| !wrapper.is_empty());
+        let _ = settings.set("browser-arguments", args);
+        let _ = settings.set("browser-environment", env);
+        let _ = settings.set("browser-wrappers", wrappers);
+    }
+
+    /// Adds `other` on top of these options, e.g. options of a rule on top of options of its browser.
+    pub fn merge(&mut self, other: &LaunchOptions) {
+        self.private |= other.private;
+        self.app_mode |= other.app_mode;
+        self.throwaway |= other.throwaway;
+        self.args.extend(other.args.iter().cloned());
+        self.env.extend(other.env.iter().map(|(name, value)| (name.clone(), value.clone())));
+        if !other.container.is_empty() {
+            self.container = other.container.clone();
+        }
+        if !other.wrapper.is_empty() {
+            self.wrapper = other.wrapper.clone();
+        }
+        if !other.webmail.is_empty() {
+            self.webmail = other.webmail.clone();
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        !self.private && !self.app_mode && !self.throwaway && self.args.is_empty() && self.env.is_empty() && self.wrapper.is_empty()
+    }
+
+    /// Arguments the options add to the command line of `app`, fails if the browser can not do
+    /// what they ask for instead of silently opening a normal window.
+    fn args(&self, app: &AppInfo, files: &[File]) -> io::Result<Vec<String>> {
+        let mut args = Vec::new();
+        let family = BrowserFamily::of(app);
+        if self.private {
+            let family = family.ok_or_else(|| unsupported(format!("{} can not open a private window", app.name())))?;
+            args.push(family.private_flag().to_string());
+        }
+        if self.app_mode {
+            let profile = files.first().filter(|_| self.app_profile).and_then(|file| app_profile_dir(app, file));
+            let app_args = family.and_then(|family| family.app_args(profile.as_ref()))
+                .ok_or_else(|| unsupported(format!("{} can not open a link as an app", app.name())))?;
+            args.extend(app_args);
+        }
+        args.extend(self.args.iter().cloned());
+        Ok(args)
+    }
+}
+
+
+fn unsupported(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, reason)
+}
+
+
+/// Returns a directory for the profile of `app` used to open `file` as an app, creating it if needed.
+fn app_profile_dir(app: &AppInfo, file: &File) -> Option<PathBuf> {
+    let host = glib::Uri::parse(&file.uri(), glib::UriFlags::NONE).ok()?.host()?;
+    let dir = glib::user_data_dir()
+        .join("browdi")
+        .join("app-profiles")
+        .join(app.id()?.trim_end_matches(".desktop"))
+        .join(host.replace('/', "_"));
+    fs::create_dir_all(&dir).ok()?;
+    Some(dir)
+}
+
+
+/// Parses arguments written as in a shell, e.g. `--new-window --class="Work browser"`.
+pub fn parse_args(text: &str) -> Option<Vec<String>> {
+    if text.trim().is_empty() {
+        return Some(Vec::new());
+    }
+    let args = glib::shell_parse_argv(text).ok()?;
+    Some(args.iter().map(|arg| arg.to_string_lossy().into_owned()).collect())
+}
+
+
+pub fn format_args(args: &[String]) -> String {
+    let quote = |arg: &String| match arg.chars().all(|c| c.is_alphanumeric() || "-_=./:,%@+".contains(c)) {
+        true => arg.clone(),
+        false => glib::shell_quote(arg).to_string_lossy().into_owned(),
+    };
+    args.iter().map(quote).collect::<Vec<String>>().join(" ")
+}
+
+
+/// Parses variables written as `NAME=value` separated with spaces, values can be quoted as in a shell.
+pub fn parse_env(text: &str) -> Option<HashMap<String, String>> {
+    parse_args(text)?
+        .into_iter()
+        .map(|variable| {
+            let (name, value) = variable.split_once('=')?;
+            let valid = !name.is_empty() && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            valid.then(|| (name.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+
+/// Checks if the browser started in `wrapper` can not write to `dir`, because the sandbox puts
+/// a temporary or read only directory over it. Only bubblewrap and firejail are known.
+fn hides_dir(wrapper: &[String], dir: &Path) -> bool {
+    let Some((program, args)) = wrapper.split_first() else { return false };
+    match Path::new(program).file_name().and_then(|name| name.to_str()) {
+        Some("firejail") => {
+            args.iter().any(|arg| arg == "--private" || arg.starts_with("--private="))
+                && dir.starts_with(glib::home_dir())
+        },
+        Some("bwrap") => {
+            // without a mount over it the directory does not exist in the sandbox
+            let mut hidden = true;
+            let mut args = args.iter();
+            while let Some(arg) = args.next() {
+                let (writable, source, destination) = match arg.as_str() {
+                    "--tmpfs" => (false, None, args.next()),
+                    "--bind" | "--bind-try" | "--dev-bind" | "--dev-bind-try" => (true, args.next(), args.next()),
+                    "--ro-bind" | "--ro-bind-try" => (false, args.next(), args.next()),
+                    _ => continue,
+                };
+                let Some(destination) = destination.filter(|destination| dir.starts_with(destination)) else { continue };
+                hidden = !writable || source != Some(destination);
+            }
+            hidden
+        },
+        _ => false,
+    }
+}
+
+
+/// Checks that the program of `wrapper` is installed, returns the reason if it is not.
+pub fn check_wrapper(wrapper: &[String]) -> Result<(), String> {
+    match wrapper.first() {
+        Some(program) if glib::find_program_in_path(program).is_none() => Err(format!("{} is not installed", program)),
+        _ => Ok(()),
+    }
+}
+
+
+pub fn format_env(env: &HashMap<String, String>) -> String {
+    let mut variables: Vec<String> = env.iter().map(|(name, value)| format!("{name}={value}")).collect();
+    variables.sort();
+    format_args(&variables)
+}
+
+
+/// Expands the `Exec` line of `app` into an argument vector, `extra_args` are inserted right
+/// before the urls so they are passed to the browser itself and not to a wrapper like `flatpak`.
+/// `%u` in `extra_args` is replaced with the first url and an argument with `%U` is repeated for
+/// every url, e.g. `--app=%U`, the urls are then passed only there.
+pub fn command_line(app: &AppInfo, files: &[File], extra_args: &[String]) -> Option<Vec<OsString>> {
+    let commandline = app.commandline()?;
+    let uris: Vec<String> = files.iter().map(|file| file.uri().into()).collect();
+    let urls_in_extra_args = extra_args.iter().any(|arg| arg.contains("%u") || arg.contains("%U"));
+    let extra_args: Vec<String> = extra_args.iter()
+        .flat_map(|arg| match arg.contains("%U") {
+            true => uris.iter().map(|uri| arg.replace("%U", uri)).collect(),
+            false => vec![arg.replace("%u", uris.first().map(String::as_str).unwrap_or_default())],
+        })
+        .collect();
+    let mut argv = Vec::new();
+    let mut extra_args = Some(&extra_args[..]);
+    for arg in gio::glib::shell_parse_argv(commandline).ok()? {
+        let arg = arg.to_string_lossy().to_string();
+        let expanded: Vec<String> = match arg.as_str() {
+            "%u" | "%f" | "%U" | "%F" if urls_in_extra_args => Vec::new(),
+            "%u" | "%f" => uris.iter().take(1).cloned().collect(),
+            "%U" | "%F" => uris.clone(),
+            // flatpak forwards files between `@@u` and `@@`, the markers stay even without urls
+            "@@u" | "@@f" => vec![arg],
+            "%i" | "%c" | "%k" | "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => continue,
+            _ => {
+                argv.push(OsString::from(arg.replace("%%", "%")));
+                continue;
+            },
+        };
+        if let Some(extra_args) = extra_args.take() {
+            argv.extend(extra_args.iter().map(OsString::from));
+        }
+        argv.extend(expanded.into_iter().map(OsString::from));
+    }
+    if let Some(extra_args) = extra_args {
+        argv.extend(extra_args.iter().map(OsString::from));
+    }
+    Some(argv)
+}
+
+
+fn spawn(argv: &[OsString], env: &HashMap<String, String>) -> io::Result<Child> {
+    let (program, args) = argv.split_first().ok_or(io::ErrorKind::InvalidInput)?;
+    Command::new(program).args(args).envs(env).spawn()
+}
+
+
+/// Opens `files` in `app`, the desktop file is launched as is unless `options` require
+/// changes to its command line. Returns the session to watch if a throwaway session was started.
+pub fn launch(app: &AppInfo, files: &[File], options: &LaunchOptions) -> io::Result<Option<ThrowawaySession>> {
+    let container_files: Vec<File>;
+    let files = match options.container.is_empty() || BrowserFamily::of(app) != Some(BrowserFamily::Firefox) {
+        true => files,
+        false => {
+            container_files = files.iter()
+                .map(|file| File::for_uri(&containers::container_url(&options.container, &file.uri())))
+                .collect();
+            &container_files
+        },
+    };
+    if options.is_default() {
+        return app.launch(files, None::<&gio::AppLaunchContext>).map(|_| None).map_err(io::Error::other);
+    }
+    // A missing sandbox must not silently open the link unsandboxed.
+    check_wrapper(&options.wrapper).map_err(|reason| io::Error::new(io::ErrorKind::NotFound, reason))?;
+    let home = glib::home_dir().to_string_lossy().into_owned();
+    let wrapper: Vec<String> = options.wrapper.iter().map(|arg| arg.replace("$HOME", &home)).collect();
+    if options.throwaway && hides_dir(&wrapper, &throwaway_root()) {
+        return Err(unsupported(String::from("the sandbox hides the profile of the throwaway session")));
+    }
+    // the profile of the app would be passed next to the temporary one
+    if options.throwaway && options.app_mode && options.app_profile {
+        return Err(unsupported(String::from("an app with a separate profile can not be opened in a throwaway session")));
+    }
+    let mut args = options.args(app, files)?;
+    let mut profile = None;
+    if options.throwaway {
+        let throwaway_unsupported = || unsupported(format!("{} can not open a throwaway session", app.name()));
+        let family = BrowserFamily::of(app).ok_or_else(throwaway_unsupported)?;
+        let dir = throwaway_profile_dir()?;
+        let Some(profile_args) = family.profile_args(&dir) else {
+            let _ = fs::remove_dir(&dir);
+            return Err(throwaway_unsupported());
+        };
+        args.splice(0..0, profile_args);
+        profile = Some(dir);
+    }
+    let spawned = command_line(app, files, &args)
+        .ok_or(io::Error::from(io::ErrorKind::NotFound))
+        .and_then(|command| {
+            let argv: Vec<OsString> = wrapper.iter()
+                .map(OsString::from)
+                .chain(command)
+                .collect();
+            spawn(&argv, &options.env)
+        });
+    match (spawned, profile) {
+        (Ok(child), Some(profile)) => Ok(Some(ThrowawaySession { pid: glib::Pid(child.id() as i32), profile })),
+        (Ok(child), None) => {
+            // reaps the browser when it exits, it would stay a zombie until BrowDi quits otherwise
+            glib::child_watch_add_local(glib::Pid(child.id() as i32), |_, _| {});
+            Ok(None)
+        },
+        (Err(error), profile) => {
+            if let Some(profile) = profile {
+                let _ = fs::remove_dir_all(profile);
+            }
+            Err(error)
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Desktop file of a browser started with `exec`, its program has to be installed.
+    fn app(exec: &str) -> AppInfo {
+        let keyfile = glib::KeyFile::new();
+        let data = format!("[Desktop Entry]\nType=Application\nName=Browser\nExec={exec}\n");
+        keyfile.load_from_data(&data, glib::KeyFileFlags::NONE).unwrap();
+        gio::DesktopAppInfo::from_keyfile(&keyfile).unwrap().upcast()
+    }
+
+    fn files(uris: &[&str]) -> Vec<File> {
+        uris.iter().map(|uri| File::for_uri(uri)).collect()
+    }
+
+    fn command(exec: &str, uris: &[&str], extra_args: &[&str]) -> Vec<String> {
+        let extra_args: Vec<String> = extra_args.iter().map(|arg| arg.to_string()).collect();
+        command_line(&app(exec), &files(uris), &extra_args).unwrap()
+            .into_iter()
+            .map(|arg| arg.into_string().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn inserts_extra_args_before_urls() {
+        assert_eq!(
+            command("env firefox %u", &["https://example.com/a"], &["--new-window"]),
+            ["env", "firefox", "--new-window", "https://example.com/a"],
+        );
+        assert_eq!(command("env firefox %U", &[], &["--new-window"]), ["env", "firefox", "--new-window"]);
+        assert_eq!(command("env firefox --name=%c %i %u", &["https://example.com/a"], &[]), ["env", "firefox", "--name=%c", "https://example.com/a"]);
+    }
+
+    #[test]
+    fn passes_urls_only_in_extra_args_that_ask_for_them() {
+        assert_eq!(
+            command("env chromium %U", &["https://example.com/a", "https://example.com/b"], &["--app=%u"]),
+            ["env", "chromium", "--app=https://example.com/a"],
+        );
+        assert_eq!(
+            command("env chromium %U", &["https://example.com/a", "https://example.com/b"], &["--app=%U"]),
+            ["env", "chromium", "--app=https://example.com/a", "--app=https://example.com/b"],
+        );
+        assert_eq!(
+            command("env chromium %U", &["https://example.com/a", "https://example.com/b"], &["--new-window", "%U"]),
+            ["env", "chromium", "--new-window", "https://example.com/a", "https://example.com/b"],
+        );
+    }
+
+    #[test]
+    fn keeps_flatpak_file_forwarding_markers() {
+        let exec = "env flatpak run --file-forwarding org.mozilla.firefox @@u %U @@";
+        assert_eq!(
+            command(exec, &["https://example.com/a"], &["--new-window"]),
+            ["env", "flatpak", "run", "--file-forwarding", "org.mozilla.firefox", "--new-window", "@@u", "https://example.com/a", "@@"],
+        );
+        assert_eq!(
+            command(exec, &["https://example.com/a"], &["--kiosk=%u"]),
+            ["env", "flatpak", "run", "--file-forwarding", "org.mozilla.firefox", "--kiosk=https://example.com/a", "@@u", "@@"],
+        );
+    }
+
+    #[test]
+    fn finds_sandboxes_hiding_a_directory() {
+        let home = glib::home_dir();
+        let dir = home.join(".cache/browdi/throwaway/1-2");
+        let wrapper = |command: &str| -> Vec<String> {
+            parse_args(&command.replace("$HOME", &home.to_string_lossy())).unwrap()
+        };
+        let (_, bwrap) = WRAPPER_PRESETS[2];
+        assert!(!hides_dir(&wrapper(bwrap), &dir));
+        assert!(hides_dir(&wrapper("bwrap --ro-bind / / --tmpfs $HOME --unshare-pid"), &dir));
+        assert!(hides_dir(&wrapper("bwrap --ro-bind / / --unshare-pid"), &dir));
+        assert!(hides_dir(&wrapper("bwrap --bind /tmp $HOME/.cache"), &dir));
+        assert!(!hides_dir(&wrapper("bwrap --bind / / --unshare-pid"), &dir));
+        assert!(hides_dir(&wrapper("firejail --private"), &dir));
+        assert!(hides_dir(&wrapper("firejail --private=/tmp/home"), &dir));
+        assert!(!hides_dir(&wrapper("firejail --net=none"), &dir));
+        assert!(!hides_dir(&wrapper("/usr/bin/nice -n 10"), &dir));
+    }
+
+    #[test]
+    fn parses_args_as_in_a_shell() {
+        assert_eq!(parse_args("--new-window --class=\"Work browser\""), Some(vec![String::from("--new-window"), String::from("--class=Work browser")]));
+        assert_eq!(parse_args("  "), Some(Vec::new()));
+        assert_eq!(parse_args("--class=\"Work"), None);
+        let args = vec![String::from("--app=%u"), String::from("Work browser")];
+        assert_eq!(parse_args(&format_args(&args)), Some(args));
+    }
+
+    #[test]
+    fn parses_env() {
+        assert_eq!(
+            parse_env("MOZ_ENABLE_WAYLAND=1 GTK_THEME='Adwaita:dark' EMPTY="),
+            Some(HashMap::from([
+                (String::from("MOZ_ENABLE_WAYLAND"), String::from("1")),
+                (String::from("GTK_THEME"), String::from("Adwaita:dark")),
+                (String::from("EMPTY"), String::new()),
+            ])),
+        );
+        assert_eq!(parse_env(""), Some(HashMap::new()));
+        assert_eq!(parse_env("1PASSWORD=1"), None);
+        assert_eq!(parse_env("=1"), None);
+        assert_eq!(parse_env("WAYLAND"), None);
+        assert_eq!(parse_env("MOZ-WAYLAND=1"), None);
+    }
+}
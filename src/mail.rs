@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use gio::{glib, File, Settings};
+
+
+/// Content type of `mailto:` links, mail clients are registered for it.
+pub const MAILTO_TYPE: &str = "x-scheme-handler/mailto";
+
+
+/// Message a `mailto:` link starts, fields with several addresses are separated with commas.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mailto {
+    pub to: Vec<String>,
+    pub cc: Vec<String>,
+    pub bcc: Vec<String>,
+    pub subject: String,
+    pub body: String,
+}
+
+
+fn unescape(text: &str) -> String {
+    glib::Uri::unescape_string(text, None::<&str>).map(String::from).unwrap_or_else(|| text.to_string())
+}
+
+
+fn addresses(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(',').map(|address| unescape(address).trim().to_string()).filter(|address| !address.is_empty())
+}
+
+
+impl Mailto {
+    /// Parses a link like `mailto:a@example.com?cc=b@example.com&subject=Hi`.
+    pub fn parse(url: &str) -> Option<Self> {
+        let (scheme, rest) = url.split_once(':')?;
+        if !scheme.eq_ignore_ascii_case("mailto") {
+            return None;
+        }
+        let (to, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let mut mailto = Mailto { to: addresses(to).collect(), ..Mailto::default() };
+        for (name, value) in query.split('&').filter_map(|field| field.split_once('=')) {
+            match name.to_lowercase().as_str() {
+                "to" => mailto.to.extend(addresses(value)),
+                "cc" => mailto.cc.extend(addresses(value)),
+                "bcc" => mailto.bcc.extend(addresses(value)),
+                "subject" => mailto.subject = unescape(value),
+                "body" => mailto.body = unescape(value),
+                _ => {},
+            }
+        }
+        Some(mailto)
+    }
+
+    /// Url of a webmail compose page made from `template`, `{to}`, `{cc}`, `{bcc}`, `{subject}`
+    /// and `{body}` in it are replaced with the fields of the message.
+    pub fn compose_url(&self, template: &str) -> String {
+        let escape = |text: &str| glib::Uri::escape_string(text, None, false).to_string();
+        template
+            .replace("{to}", &escape(&self.to.join(",")))
+            .replace("{cc}", &escape(&self.cc.join(",")))
+            .replace("{bcc}", &escape(&self.bcc.join(",")))
+            .replace("{subject}", &escape(&self.subject))
+            .replace("{body}", &escape(&self.body))
+    }
+}
+
+
+pub fn is_mailto(file: &File) -> bool {
+    file.uri_scheme().is_some_and(|scheme| scheme == "mailto")
+}
+
+
+/// Domain of the first recipient of a `mailto:` link, rules for mail links match it.
+pub fn recipient_domain(file: &File) -> Option<String> {
+    let mailto = Mailto::parse(&file.uri())?;
+    let (_, domain) = mailto.to.first()?.rsplit_once('@')?;
+    Some(domain.trim_end_matches('>').to_lowercase())
+}
+
+
+/// Names of configured webmails, ordered by name.
+pub fn webmails(settings: &Settings) -> Vec<String> {
+    let templates: HashMap<String, String> = settings.get("webmail-templates");
+    let mut names: Vec<String> = templates.into_keys().collect();
+    names.sort();
+    names
+}
+
+
+/// File a browser is given for `file`: a `mailto:` link becomes the compose page of `webmail`,
+/// or of the default webmail if it is empty. Other links stay as they are.
+pub fn for_browser(settings: &Settings, file: &File, webmail: &str) -> File {
+    let Some(mailto) = Mailto::parse(&file.uri()) else { return file.clone() };
+    let templates: HashMap<String, String> = settings.get("webmail-templates");
+    let default = settings.string("default-webmail");
+    let webmail = if webmail.is_empty() { default.as_str() } else { webmail };
+    match templates.get(webmail) {
+        Some(template) => File::for_uri(&mailto.compose_url(template)),
+        None => file.clone(),
+    }
+}
+
+
+/// Formats webmail templates one per line as a name and an url.
+pub fn format_templates(templates: &HashMap<String, String>) -> String {
+    let mut lines: Vec<String> = templates.iter().map(|(name, url)| format!("{name} {url}")).collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+
+/// Parses webmail templates written one per line as a name and an url, the url is the last word
+/// of a line so names may contain spaces. Returns `None` if any of them is invalid.
+pub fn parse_templates(text: &str) -> Option<HashMap<String, String>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (name, url) = line.trim().rsplit_once(char::is_whitespace)?;
+            let name = name.trim();
+            (!name.is_empty() && url.contains("{to}")).then(|| (name.to_string(), url.to_string()))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mailto_links() {
+        assert_eq!(
+            Mailto::parse("mailto:a@example.com,%20b@example.com?CC=c@example.com&bcc=d@example.com,e@example.com&subject=Hello%20there&body=Line%201%0ALine%202"),
+            Some(Mailto {
+                to: vec![String::from("a@example.com"), String::from("b@example.com")],
+                cc: vec![String::from("c@example.com")],
+                bcc: vec![String::from("d@example.com"), String::from("e@example.com")],
+                subject: String::from("Hello there"),
+                body: String::from("Line 1\nLine 2"),
+            }),
+        );
+        assert_eq!(
+            Mailto::parse("MAILTO:?to=a@example.com&unknown=1&broken"),
+            Some(Mailto { to: vec![String::from("a@example.com")], ..Mailto::default() }),
+        );
+        assert_eq!(Mailto::parse("mailto:"), Some(Mailto::default()));
+        assert_eq!(Mailto::parse("https://example.com/?to=a@example.com"), None);
+        assert_eq!(Mailto::parse("a@example.com"), None);
+    }
+
+    #[test]
+    fn composes_webmail_urls() {
+        let mailto = Mailto::parse("mailto:a@example.com,b@example.com?subject=Q%26A%20%231&body=Hi%20%3D%29").unwrap();
+        assert_eq!(
+            mailto.compose_url("https://mail.example.com/compose?to={to}&cc={cc}&su={subject}&body={body}"),
+            "https://mail.example.com/compose?to=a%40example.com%2Cb%40example.com&cc=&su=Q%26A%20%231&body=Hi%20%3D%29",
+        );
+    }
+
+    #[test]
+    fn finds_recipient_domains() {
+        let domain = |uri: &str| recipient_domain(&File::for_uri(uri));
+        assert_eq!(domain("mailto:Someone@Work.Example.com?cc=other@example.org").as_deref(), Some("work.example.com"));
+        assert_eq!(domain("mailto:?to=someone@example.org").as_deref(), Some("example.org"));
+        assert_eq!(domain("mailto:%3Csomeone@example.org%3E").as_deref(), Some("example.org"));
+        assert_eq!(domain("mailto:?subject=Hi"), None);
+        assert_eq!(domain("mailto:someone"), None);
+    }
+}
@@ -1,17 +1,50 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-
-use gtk::glib::clone;
+use std::io;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+mod containers;
+mod groups;
+mod handoff;
+mod history;
+mod history_window;
+mod hotkeys;
+mod launch;
+mod mail;
+mod media;
+mod preferences;
+mod processes;
+mod qr;
+mod queries;
+mod rules;
+mod search;
+mod sources;
+mod suggestions;
+mod urls;
+
+use gtk::glib::{self, clone};
 use gtk::prelude::*;
 use relm4::{adw, SharedState};
 use gio::{AppInfo, File, Settings};
 use gio::prelude::{AppInfoExt, FileExt};
 use relm4::factory::FactoryVecDeque;
 use relm4::{gtk, ComponentParts, ComponentSender, RelmApp, RelmWidgetExt, SimpleComponent};
-use itertools::{Itertools, EitherOrBoth};
-use gio;
+use itertools::Itertools;
 use relm4::prelude::*;
 use relm4::gtk::prelude::{ButtonExt, WidgetExt, BoxExt, GtkWindowExt, ToggleButtonExt, ApplicationExtManual, ApplicationExt};
 
+use crate::handoff::Handoff;
+use crate::history::{DispatchOrigin, HistoryEntry};
+use crate::history_window::{HistoryInit, HistoryInputMessage, HistoryModel, HistoryOutputMessage};
+use crate::hotkeys::{LaunchAction, LaunchModifier};
+use crate::launch::{BrowserFamily, LaunchOptions};
+use crate::processes::RunningProcesses;
+use crate::sources::Source;
+use crate::suggestions::BrowserOrder;
+use crate::urls::CopyFormat;
+use crate::preferences::{PreferencesInit, PreferencesInputMessage, PreferencesModel, PreferencesOutputMessage};
+
 
 const EXCLUDED_APPS: [&str; 4] = [
     "com.Nosterx.BrowDi",
@@ -24,9 +57,35 @@ const EXCLUDED_APPS: [&str; 4] = [
 static APP_STATE: SharedState<bool> = SharedState::new();
 
 
+const COUNTDOWN_TICK: Duration = Duration::from_millis(50);
+
+
+const STYLE: &str = "
+button.browser-button.selected {
+    box-shadow: inset 0 0 0 3px @accent_bg_color;
+}
+
+button.browser-button.marked {
+    background-color: alpha(@accent_bg_color, 0.35);
+}
+
+label.running-badge {
+    padding: 2px 6px;
+    border-radius: 4px;
+    background-color: @success_bg_color;
+    color: @success_fg_color;
+    font-size: smaller;
+}
+";
+
+
 #[derive(Debug)]
 struct BrowserButtonInit {
     hotkey: Option<char>,
+    /// Actions offered in the context menu of the button.
+    actions: Vec<LaunchAction>,
+    /// Firefox containers offered below the button.
+    containers: Vec<String>,
     icon: gio::Icon,
     name: String,
     width: u16,
@@ -51,18 +110,37 @@ struct BrowserButton {
     margin_end: u16,
     show_hotkey_help: bool,
     hotkey_help_label: gtk::Label,
+    menu: gtk::Popover,
+    containers_box: gtk::Box,
+    selected: bool,
+    marked: bool,
+    visible: bool,
+    progress: Option<f64>,
+    running: bool,
 }
 
 
 #[derive(Debug)]
 enum BrowserButtonOutputMessage {
-    Pressed(DynamicIndex),
+    Pressed(DynamicIndex, gtk::gdk::ModifierType),
+    ActionChosen(DynamicIndex, LaunchAction),
+    ContainerChosen(DynamicIndex, String),
+    MiddleClicked(DynamicIndex),
+    Focused(DynamicIndex),
 }
 
 
 #[derive(Debug)]
 enum BrowserButtonInputMessage {
     Update,
+    SetHotkey(Option<char>),
+    SetSelected(bool),
+    SetMarked(bool),
+    SetVisible(bool),
+    SetProgress(Option<f64>),
+    SetRunning(bool),
+    Focus,
+    ShowMenu,
 }
 
 
@@ -77,7 +155,13 @@ impl FactoryComponent for BrowserButton {
     view! {
         #[root]
         gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+
+            #[track(self.changed(BrowserButton::visible()))]
+            set_visible: self.visible,
+
             gtk::Overlay {
+                #[name = "button"]
                 gtk::Button {
                     set_margin_top: self.margin_top.into(),
                     set_margin_bottom: self.margin_bottom.into(),
@@ -86,29 +170,92 @@ impl FactoryComponent for BrowserButton {
                     set_width_request: self.width.into(),
                     set_height_request: self.height.into(),
                     set_tooltip_text: Some(self.name.clone()).as_deref(),
+                    add_css_class: "browser-button",
+
+                    #[track(self.changed(BrowserButton::selected()))]
+                    set_class_active: ("selected", self.selected),
+
+                    #[track(self.changed(BrowserButton::marked()))]
+                    set_class_active: ("marked", self.marked),
 
                     gtk::Image::from_gicon(&self.icon) {
                         set_pixel_size: self.height.into(),
                     },
 
-                    connect_clicked[sender, index] => move |_| {
-                        sender.output(BrowserButtonOutputMessage::Pressed(index.clone())).unwrap();
+                    connect_clicked[sender, index] => move |button| {
+                        let state = button.display()
+                            .default_seat()
+                            .and_then(|seat| seat.keyboard())
+                            .map(|keyboard| keyboard.modifier_state())
+                            .unwrap_or(gtk::gdk::ModifierType::empty());
+                        sender.output(BrowserButtonOutputMessage::Pressed(index.clone(), state)).unwrap();
+                    },
+
+                    add_controller = gtk::GestureClick {
+                        set_button: gtk::gdk::BUTTON_MIDDLE,
+
+                        connect_released[sender, index] => move |_, _, _, _| {
+                            sender.output(BrowserButtonOutputMessage::MiddleClicked(index.clone())).unwrap();
+                        },
+                    },
+
+                    add_controller = gtk::GestureClick {
+                        set_button: gtk::gdk::BUTTON_SECONDARY,
+
+                        connect_pressed[sender] => move |_, _, _, _| {
+                            sender.input(BrowserButtonInputMessage::ShowMenu);
+                        },
+                    },
+
+                    connect_has_focus_notify[sender, index] => move |button| {
+                        if button.has_focus() {
+                            sender.output(BrowserButtonOutputMessage::Focused(index.clone())).unwrap();
+                        }
                     },
                 },
 
+                add_overlay = &gtk::ProgressBar {
+                    set_valign: gtk::Align::End,
+                    set_margin_bottom: self.margin_end.into(),
+                    set_margin_start: self.margin_end.into(),
+                    set_margin_end: (self.margin_end * 2).into(),
+                    set_can_target: false,
+
+                    #[track(self.changed(BrowserButton::progress()))]
+                    set_visible: self.progress.is_some(),
+
+                    #[track(self.changed(BrowserButton::progress()))]
+                    set_fraction: self.progress.unwrap_or_default(),
+                },
+
+                add_overlay = &gtk::Label {
+                    set_label: "running",
+                    set_halign: gtk::Align::End,
+                    set_valign: gtk::Align::Start,
+                    set_margin_top: self.margin_end.into(),
+                    set_margin_end: (self.margin_end * 2).into(),
+                    set_can_target: false,
+                    add_css_class: "running-badge",
+
+                    #[track(self.changed(BrowserButton::running()))]
+                    set_visible: self.running,
+                },
+
                 #[track({self.changed(BrowserButton::show_hotkey_help()) && self.show_hotkey_help})]
                 add_overlay: &self.hotkey_help_label,
 
                 #[track({self.changed(BrowserButton::show_hotkey_help()) && !self.show_hotkey_help})]
                 remove_overlay: &self.hotkey_help_label,
-            }
+            },
+
+            append: &self.containers_box,
         }
     }
 
-    fn init_model(init: Self::Init, _index: &DynamicIndex, sender: FactorySender<Self>) -> Self {
+    fn init_model(init: Self::Init, index: &DynamicIndex, sender: FactorySender<Self>) -> Self {
         APP_STATE.subscribe(sender.input_sender(), |_| BrowserButtonInputMessage::Update);
         let hotkey_help_label = gtk::Label::builder()
-            .label(format!("{}", init.hotkey.map(|x| x.to_string()).as_deref().unwrap_or("")))
+            .label(init.hotkey.map(String::from).unwrap_or_default())
             .halign(gtk::Align::Start)
             .valign(gtk::Align::Start)
             .margin_top(init.margin_end.into())
@@ -124,6 +271,49 @@ impl FactoryComponent for BrowserButton {
              border-radius: 4px;"
         );
 
+        let menu = gtk::Popover::new();
+        let actions = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        for action in init.actions {
+            let button = gtk::Button::builder()
+                .label(action.description())
+                .css_classes(vec!["flat"])
+                .build();
+            button.connect_clicked(clone!(@strong sender, @strong index, @weak menu => move |_| {
+                menu.popdown();
+                sender.output(BrowserButtonOutputMessage::ActionChosen(index.clone(), action)).unwrap();
+            }));
+            actions.append(&button);
+        }
+        let containers_box = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .margin_end(init.margin_end.into())
+            .visible(!init.containers.is_empty())
+            .build();
+        if !init.containers.is_empty() {
+            actions.append(&gtk::Separator::new(gtk::Orientation::Horizontal));
+        }
+        for container in init.containers {
+            let container_button = gtk::Button::builder()
+                .label(&container)
+                .tooltip_text(format!("Open in the {} container", container))
+                .css_classes(vec!["flat"])
+                .build();
+            container_button.connect_clicked(clone!(@strong sender, @strong index, @strong container => move |_| {
+                sender.output(BrowserButtonOutputMessage::ContainerChosen(index.clone(), container.clone())).unwrap();
+            }));
+            containers_box.append(&container_button);
+            let menu_button = gtk::Button::builder()
+                .label(format!("Open in the {} container", container))
+                .css_classes(vec!["flat"])
+                .build();
+            menu_button.connect_clicked(clone!(@strong sender, @strong index, @weak menu => move |_| {
+                menu.popdown();
+                sender.output(BrowserButtonOutputMessage::ContainerChosen(index.clone(), container.clone())).unwrap();
+            }));
+            actions.append(&menu_button);
+        }
+        menu.set_child(Some(&actions));
+
         Self {
             icon: init.icon,
             name: init.name,
@@ -135,33 +325,111 @@ impl FactoryComponent for BrowserButton {
             margin_end: init.margin_end,
             show_hotkey_help: false,
             hotkey_help_label,
+            menu,
+            containers_box,
+            selected: false,
+            marked: false,
+            visible: true,
+            progress: None,
+            running: false,
             tracker: 0,
         }
     }
 
-    fn update(&mut self, msg: Self::Input, _sender: FactorySender<Self>) {
+    fn update_with_view(&mut self, widgets: &mut Self::Widgets, msg: Self::Input, sender: FactorySender<Self>) {
         self.reset();
         match msg {
             BrowserButtonInputMessage::Update => {
                 self.set_show_hotkey_help(*APP_STATE.read());
             },
+            BrowserButtonInputMessage::SetHotkey(hotkey) => {
+                self.hotkey_help_label.set_label(&hotkey.map(String::from).unwrap_or_default());
+            },
+            BrowserButtonInputMessage::SetSelected(selected) => {
+                self.set_selected(selected);
+            },
+            BrowserButtonInputMessage::SetMarked(marked) => {
+                self.set_marked(marked);
+            },
+            BrowserButtonInputMessage::SetVisible(visible) => {
+                self.set_visible(visible);
+            },
+            BrowserButtonInputMessage::SetProgress(progress) => {
+                self.set_progress(progress);
+            },
+            BrowserButtonInputMessage::SetRunning(running) => {
+                self.set_running(running);
+            },
+            BrowserButtonInputMessage::Focus => {
+                widgets.button.grab_focus();
+            },
+            BrowserButtonInputMessage::ShowMenu => {
+                if self.menu.parent().is_none() {
+                    self.menu.set_parent(&widgets.button);
+                }
+                self.menu.popup();
+            },
         }
+        self.update_view(widgets, sender);
     }
 }
 
 
 #[derive(Debug, Clone)]
 enum AppInputMessage {
-    BrowserButtonPressed(usize),
+    BrowserButtonPressed(usize, LaunchAction),
+    BrowserContainerChosen(usize, String),
+    BrowserButtonClicked(usize, gtk::gdk::ModifierType),
+    BrowserButtonMiddleClicked(usize),
+    BrowserButtonFocused(usize),
     DomainToggleToggled(bool),
-    FilesOpenRequested(Vec<File>),
+    FilesOpenRequested(Vec<File>, Option<Source>),
     CurrentFileChanged,
-    KeyPressed(gtk::gdk::Key),
+    KeyPressed(gtk::gdk::Key, u32, gtk::gdk::ModifierType),
     Quit,
     ShowFullUrlToggleToggled(bool),
+    UrlEditStarted,
+    UrlEdited(String),
+    UrlEditCancelled,
+    CopyRequested(CopyFormat),
+    QrCodeToggled,
+    LauncherTextChanged(String),
+    LauncherUrlEntered(String),
+    LinksDropped(Vec<File>),
+    ContentTypeDetected(String, Option<String>),
+    HandlerChosen(usize),
+    HandoffChosen,
+    WebmailSelected(u32),
     MenuOpened,
+    ShowPreferences,
+    HotkeysChanged,
+    ModifierActionsChanged,
+    SearchChanged(String),
+    SearchActivated,
+    SearchStopped,
+    ShowHistory,
+    HistoryReopen(String, String),
+    RulePromptAccepted,
+    RulePromptDismissed,
+    BrowserOrderChanged,
+    CountdownTick,
+    UserInteracted,
+    ThrowawaySessionEnded,
+    OpenMarked,
+    OpenGroup(usize),
+    SaveGroup(String),
+    GroupsChanged,
+}
+
+/// Opens the current url in `target` when `duration` passes without the user doing anything.
+struct Countdown {
+    target: usize,
+    started: Instant,
+    duration: Duration,
+    timer: gtk::glib::SourceId,
 }
 
+
 struct BrowDiInit {
     padding: u16,
     spacing: u16,
@@ -202,15 +470,414 @@ struct BrowDiModel {
     is_domain_toggle_visible: bool,
     current_uri: Option<String>,
     current_domain: Option<String>,
+    source: Option<Source>,
     settings: Settings,
     show_keyboard_shortcuts_tooltips: bool,
     show_full_url: bool,
+    editing_url: bool,
+    /// Url shown as a QR code to open it on a phone.
+    qr_code: Option<String>,
+    #[do_not_track]
+    toasts: adw::ToastOverlay,
+    /// Text of the entry shown when BrowDi is started without a url.
+    #[do_not_track]
+    launcher_text: String,
+    #[do_not_track]
+    launcher_entry: gtk::Entry,
     default_for_domain_toggle_label: gtk::Label,
     menu_label: gtk::Label,
     activate_menu: bool,
     #[do_not_track]
     buttons: FactoryVecDeque<BrowserButton>,
-    hotkeys: Vec<char>,
+    #[do_not_track]
+    preferences: Controller<PreferencesModel>,
+    #[do_not_track]
+    history: Controller<HistoryModel>,
+    hotkeys: HashMap<String, Option<char>>,
+    selected: usize,
+    modifier_actions: HashMap<LaunchModifier, LaunchAction>,
+    modifier_actions_help: String,
+    search_visible: bool,
+    search_query: String,
+    rule_prompt: Option<usize>,
+    #[do_not_track]
+    countdown: Option<Countdown>,
+    /// Number of running throwaway sessions, BrowDi hides instead of quitting until they end
+    /// to delete their profiles.
+    throwaway_sessions: usize,
+    quit_requested: bool,
+    /// Browsers the url is opened in at once, in the order they were marked.
+    marked: Vec<usize>,
+    groups: Vec<(String, Vec<String>)>,
+    #[do_not_track]
+    groups_box: gtk::Box,
+    /// Applications other than browsers offered for media files and documents.
+    #[do_not_track]
+    handlers: Vec<AppInfo>,
+    #[do_not_track]
+    handlers_box: gtk::Box,
+    /// Native client of the web app the current url links to.
+    #[do_not_track]
+    handoff: Option<Handoff>,
+    is_mailto: bool,
+    #[do_not_track]
+    webmails: Vec<String>,
+    /// Webmail `mailto:` links are composed in when opened in a browser.
+    #[do_not_track]
+    webmail: String,
+}
+
+
+impl BrowDiModel {
+    fn browser_with_id(&self, browser_id: &str) -> Option<usize> {
+        self.browsers.iter().position(|browser| browser.id().is_some_and(|id| id == browser_id))
+    }
+
+    /// Finds the browser with `hotkey` among the ones not hidden by the search.
+    fn browser_with_hotkey(&self, hotkey: char) -> Option<usize> {
+        self.visible_browsers().into_iter().find(|number| {
+            self.browsers[*number].id().is_some_and(|id| self.hotkeys.get(id.as_str()).copied().flatten() == Some(hotkey))
+        })
+    }
+
+    /// Browsers not hidden by the search in the order of their buttons.
+    fn visible_browsers(&self) -> Vec<usize> {
+        (0..self.browsers.len()).filter(|number| self.search_score(*number).is_some()).collect()
+    }
+
+    fn select(&mut self, number: usize, focus: bool) {
+        if number >= self.browsers.len() {
+            return;
+        }
+        if number != self.selected {
+            self.buttons.send(self.selected, BrowserButtonInputMessage::SetSelected(false));
+        }
+        self.selected = number;
+        self.buttons.send(number, BrowserButtonInputMessage::SetSelected(true));
+        if focus {
+            self.buttons.send(number, BrowserButtonInputMessage::Focus);
+        }
+    }
+
+    fn search_score(&self, number: usize) -> Option<i32> {
+        let browser = &self.browsers[number];
+        let name_score = search::fuzzy_score(&self.search_query, &browser.name());
+        let id_score = browser.id().and_then(|id| search::fuzzy_score(&self.search_query, &id));
+        name_score.max(id_score)
+    }
+
+    /// Hides browsers not matching the search query and selects the best match.
+    fn apply_search(&mut self) {
+        let scores: Vec<Option<i32>> = (0..self.browsers.len()).map(|number| self.search_score(number)).collect();
+        for (number, score) in scores.iter().enumerate() {
+            self.buttons.send(number, BrowserButtonInputMessage::SetVisible(score.is_some()));
+        }
+        if self.search_query.is_empty() {
+            return;
+        }
+        let best = scores.iter()
+            .enumerate()
+            .filter_map(|(number, score)| score.map(|score| (number, score)))
+            .rev()
+            .max_by_key(|(_, score)| *score);
+        if let Some((number, _)) = best {
+            self.select(number, false);
+        }
+    }
+
+    fn remember_domain(&self, number: usize, file: &File) {
+        if rules::is_web_url(file) {
+            rules::remember(&self.settings, &self.browsers[number], rules::domain_of(&file.uri()));
+        }
+    }
+
+    fn toggle_mark(&mut self, number: usize) {
+        if number >= self.browsers.len() {
+            return;
+        }
+        let marked = !self.marked.contains(&number);
+        if marked {
+            self.get_mut_marked().push(number);
+        } else {
+            self.get_mut_marked().retain(|n| *n != number);
+        }
+        self.buttons.send(number, BrowserButtonInputMessage::SetMarked(marked));
+    }
+
+    fn clear_marks(&mut self) {
+        for number in std::mem::take(self.get_mut_marked()) {
+            self.buttons.send(number, BrowserButtonInputMessage::SetMarked(false));
+        }
+    }
+
+    fn reload_groups(&self, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.groups_box.first_child() {
+            self.groups_box.remove(&child);
+        }
+        for (number, (name, members)) in self.groups.iter().enumerate() {
+            let names = members.iter()
+                .filter_map(|id| self.browser_with_id(id))
+                .map(|member| self.browsers[member].name())
+                .join(", ");
+            let button = gtk::Button::builder()
+                .label(name)
+                .tooltip_text(format!("Open in {}", names))
+                .build();
+            button.connect_clicked(clone!(@strong sender => move |_| {
+                sender.input(AppInputMessage::OpenGroup(number));
+            }));
+            self.groups_box.append(&button);
+        }
+    }
+
+    /// Opens the current url in all `numbers` browsers at once and moves on to the next url or quits,
+    /// without a url the browsers are just started.
+    fn open_many(&mut self, numbers: Vec<usize>, origin: DispatchOrigin, sender: &ComponentSender<Self>) {
+        self.cancel_countdown();
+        self.push_launcher_link();
+        let file = self.files.pop();
+        let files: Vec<File> = file.iter().cloned().collect();
+        let count = self.browsers.len();
+        let mut failed = Vec::new();
+        for number in numbers.into_iter().filter(|number| *number < count) {
+            let options = self.picker_options(&self.browsers[number]);
+            match self.launch(number, &files, &options, sender) {
+                Ok(()) => {
+                    if let Some(file) = &file {
+                        self.record_dispatch(number, file, origin);
+                    }
+                },
+                Err(error) => {
+                    self.report_launch_error(number, &error);
+                    failed.push(number);
+                },
+            }
+        }
+        self.clear_marks();
+        // the browsers that failed stay marked with the url, so they can be tried again
+        if !failed.is_empty() {
+            for number in failed {
+                self.toggle_mark(number);
+            }
+            self.files.extend(file);
+            return;
+        }
+        if self.files.is_empty() {
+            sender.input(AppInputMessage::Quit);
+        } else {
+            sender.input(AppInputMessage::CurrentFileChanged);
+        }
+    }
+
+    /// Shows buttons of applications for `content_type` of the current url.
+    fn reload_handlers(&mut self, content_type: Option<&str>, sender: &ComponentSender<Self>) {
+        while let Some(child) = self.handlers_box.first_child() {
+            self.handlers_box.remove(&child);
+        }
+        self.handlers = content_type.map(|content_type| media::handlers(content_type, &self.browsers)).unwrap_or_default();
+        if let Some(handoff) = &self.handoff {
+            let button = gtk::Button::builder()
+                .label(format!("Open in {} app", handoff.service))
+                .tooltip_text(&handoff.url)
+                .css_classes(vec!["suggested-action"])
+                .build();
+            button.connect_clicked(clone!(@strong sender => move |_| {
+                sender.input(AppInputMessage::HandoffChosen);
+            }));
+            self.handlers_box.append(&button);
+        }
+        for (number, handler) in self.handlers.iter().enumerate() {
+            let button = gtk::Button::builder()
+                .label(format!("Open in {}", handler.name()))
+                .tooltip_text(content_type.unwrap_or_default())
+                .build();
+            button.connect_clicked(clone!(@strong sender => move |_| {
+                sender.input(AppInputMessage::HandlerChosen(number));
+            }));
+            self.handlers_box.append(&button);
+        }
+        self.handlers_box.set_visible(!self.handlers.is_empty() || self.handoff.is_some());
+    }
+
+    /// Opens the current url, or `url` it was rewritten to, in an application other than a browser
+    /// and moves on to the next url or quits.
+    fn open_in_app(&mut self, app: &AppInfo, url: Option<&str>, origin: DispatchOrigin, sender: &ComponentSender<Self>) {
+        self.cancel_countdown();
+        let Some(file) = self.files.pop() else { return };
+        let url = url.map(String::from).unwrap_or_else(|| file.uri().into());
+        let _ = app.launch_uris(&[&url], None::<&gio::AppLaunchContext>);
+        self.record_app_dispatch(app, &file, origin);
+        if self.files.is_empty() {
+            sender.input(AppInputMessage::Quit);
+        } else {
+            sender.input(AppInputMessage::CurrentFileChanged);
+        }
+    }
+
+    /// Moves browsers and their buttons into the configured order, keeping the same browser selected,
+    /// offered as a rule and counted down to.
+    fn apply_order(&mut self) {
+        self.clear_marks();
+        let order = suggestions::order(&self.settings, &self.browsers, self.current_domain.as_deref());
+        let id_of = |number: Option<usize>| number.and_then(|number| self.browsers.get(number)).and_then(|browser| browser.id());
+        let selected_id = id_of(Some(self.selected));
+        let rule_prompt_id = id_of(self.rule_prompt);
+        let countdown_id = id_of(self.countdown.as_ref().map(|countdown| countdown.target));
+        let mut buttons = self.buttons.guard();
+        for (position, id) in order.iter().enumerate() {
+            let Some(current) = self.browsers.iter().position(|browser| browser.id().is_some_and(|browser_id| browser_id == *id)) else { continue };
+            if current != position {
+                self.browsers[position..=current].rotate_right(1);
+                buttons.move_to(current, position);
+            }
+        }
+        drop(buttons);
+        if let Some(number) = selected_id.and_then(|id| self.browser_with_id(&id)) {
+            self.selected = number;
+        }
+        self.rule_prompt = rule_prompt_id.and_then(|id| self.browser_with_id(&id));
+        match countdown_id.and_then(|id| self.browser_with_id(&id)) {
+            Some(number) => {
+                if let Some(countdown) = self.countdown.as_mut() {
+                    countdown.target = number;
+                }
+            },
+            None => self.cancel_countdown(),
+        }
+    }
+
+    fn launch(&mut self, number: usize, files: &[File], options: &LaunchOptions, sender: &ComponentSender<Self>) -> io::Result<()> {
+        let files: Vec<File> = files.iter().map(|file| mail::for_browser(&self.settings, file, &options.webmail)).collect();
+        if let Some(session) = launch::launch(&self.browsers[number], &files, options)? {
+            self.throwaway_sessions += 1;
+            session.watch(clone!(@strong sender => move || sender.input(AppInputMessage::ThrowawaySessionEnded)));
+        }
+        Ok(())
+    }
+
+    fn report_launch_error(&self, number: usize, error: &io::Error) {
+        self.toasts.add_toast(adw::Toast::new(&format!("Could not open in {}: {}", self.browsers[number].name(), error)));
+    }
+
+    /// Options of `browser` chosen in the picker, `mailto:` links go to the webmail chosen there.
+    fn picker_options(&self, browser: &AppInfo) -> LaunchOptions {
+        LaunchOptions { webmail: self.webmail.clone(), ..LaunchOptions::load(&self.settings, browser) }
+    }
+
+    /// Opens the current url in the browser `number` and moves on to the next url or quits.
+    fn open(&mut self, number: usize, action: LaunchAction, origin: DispatchOrigin, sender: &ComponentSender<Self>) {
+        let Some(browser) = self.browsers.get(number) else { return };
+        let mut options = self.picker_options(browser);
+        options.private = action == LaunchAction::OpenPrivate;
+        options.app_mode = action == LaunchAction::OpenAsApp;
+        options.throwaway = action == LaunchAction::OpenThrowaway;
+        self.open_with(number, action, options, origin, sender);
+    }
+
+    /// Makes a link typed in the launcher the current url, it goes to the chosen browsers skipping rules.
+    fn push_launcher_link(&mut self) {
+        if let Some(url) = queries::to_url(&self.settings, &self.launcher_text).filter(|_| self.files.is_empty()) {
+            self.files.push(File::for_uri(&url));
+        }
+    }
+
+    fn open_with(&mut self, number: usize, action: LaunchAction, options: LaunchOptions, origin: DispatchOrigin, sender: &ComponentSender<Self>) {
+        if number >= self.browsers.len() {
+            return;
+        }
+        self.cancel_countdown();
+        self.push_launcher_link();
+        let file = match action {
+            LaunchAction::OpenAndKeep => self.files.last().cloned(),
+            _ => self.files.pop(),
+        };
+        if let Some(file) = file {
+            if let Err(error) = self.launch(number, std::slice::from_ref(&file), &options, sender) {
+                self.report_launch_error(number, &error);
+                if action != LaunchAction::OpenAndKeep {
+                    self.files.push(file);
+                }
+                return;
+            }
+            self.record_dispatch(number, &file, origin);
+            if let Some(browser_id) = self.browsers[number].id().filter(|_| origin == DispatchOrigin::Manual && rules::is_web_url(&file)) {
+                suggestions::record_choice(&self.settings, &rules::domain_of(&file.uri()), &browser_id);
+            }
+            if self.default_for_domain || action == LaunchAction::OpenAndRemember {
+                self.remember_domain(number, &file);
+            }
+            if action == LaunchAction::OpenAndKeep {
+                return;
+            }
+            if self.files.is_empty() {
+                sender.input(AppInputMessage::Quit);
+            } else {
+                sender.input(AppInputMessage::CurrentFileChanged);
+            }
+        } else {
+            if let Err(error) = self.launch(number, &[], &options, sender) {
+                self.report_launch_error(number, &error);
+            } else if action != LaunchAction::OpenAndKeep {
+                sender.input(AppInputMessage::Quit);
+            }
+        }
+    }
+
+    /// Starts the countdown to open the current url in the configured fallback browser or
+    /// in the `suggested` one if there is no fallback.
+    fn start_countdown(&mut self, suggested: Option<usize>, sender: &ComponentSender<Self>) {
+        self.cancel_countdown();
+        let seconds = sources::countdown_seconds(&self.settings, self.source.as_ref());
+        let target = self.browser_with_id(&self.settings.string("countdown-fallback")).or(suggested);
+        let Some(target) = target.filter(|_| seconds > 0) else { return };
+        let timer = gtk::glib::timeout_add_local(COUNTDOWN_TICK, clone!(@strong sender => move || {
+            sender.input(AppInputMessage::CountdownTick);
+            gtk::glib::ControlFlow::Continue
+        }));
+        self.buttons.send(target, BrowserButtonInputMessage::SetProgress(Some(1.0)));
+        self.countdown = Some(Countdown {
+            target,
+            started: Instant::now(),
+            duration: Duration::from_secs(seconds.into()),
+            timer,
+        });
+    }
+
+    fn cancel_countdown(&mut self) {
+        if let Some(countdown) = self.countdown.take() {
+            countdown.timer.remove();
+            self.buttons.send(countdown.target, BrowserButtonInputMessage::SetProgress(None));
+        }
+    }
+
+    fn url_tooltip(&self) -> String {
+        let uri = self.current_uri.clone().unwrap_or_default();
+        match &self.source {
+            Some(source) => format!("{}\nOpened from {}", uri, source.names.join(", ")),
+            None => uri,
+        }
+    }
+
+    fn rule_prompt_text(&self) -> String {
+        match (self.rule_prompt.and_then(|number| self.browsers.get(number)), &self.current_domain) {
+            (Some(browser), Some(domain)) => format!("Always open {} in {}?", domain, browser.name()),
+            _ => String::new(),
+        }
+    }
+
+    fn record_dispatch(&self, number: usize, file: &File, origin: DispatchOrigin) {
+        self.record_app_dispatch(&self.browsers[number], file, origin);
+    }
+
+    fn record_app_dispatch(&self, app: &AppInfo, file: &File, origin: DispatchOrigin) {
+        let entry = HistoryEntry::new(
+            file.uri().into(),
+            app.id().map(String::from).unwrap_or_default(),
+            app.name().into(),
+            origin,
+        );
+        let _ = history::record(&self.settings, entry);
+    }
 }
 
 
@@ -229,161 +896,439 @@ impl SimpleComponent for BrowDiModel {
             set_decorated: true,
             set_resizable: false,
 
-            gtk::Box {
-                set_orientation: gtk::Orientation::Vertical,
-                set_spacing: model.spacing as i32,
-                set_margin_start: model.margin as i32,
-                set_margin_top: model.margin as i32,
-                set_margin_bottom: model.margin as i32,
-                set_valign: gtk::Align::Center,
-
-                #[local]
-                browser_buttons_vbox -> gtk::Box {
-                    set_orientation: gtk::Orientation::Horizontal,
-                    set_halign: gtk::Align::Center,
-                },
+            #[watch]
+            set_visible: !model.quit_requested,
+
+            connect_close_request[sender] => move |_| {
+                sender.input(AppInputMessage::Quit);
+                glib::Propagation::Stop
+            },
 
-                gtk::CenterBox {
-                    set_margin_all: 0,
+            #[local_ref]
+            toast_overlay -> adw::ToastOverlay {
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_spacing: model.spacing as i32,
+                    set_margin_start: model.margin as i32,
+                    set_margin_top: model.margin as i32,
+                    set_margin_bottom: model.margin as i32,
+                    set_valign: gtk::Align::Center,
+
+                    gtk::SearchEntry {
+                        set_placeholder_text: Some("Search browsers"),
+                        set_margin_end: model.margin as i32,
 
-                    #[wrap(Some)]
-                    set_start_widget: default_for_domain_toggle = &gtk::Overlay {
-                        gtk::ToggleButton {
-                            set_tooltip: "Remeber selection for the domain of the url",
-                            set_margin_all: 0,
+                        #[watch]
+                        set_visible: model.search_visible,
 
-                            #[watch]
-                            set_visible: model.is_domain_toggle_visible,
+                        #[track(model.changed(BrowDiModel::search_visible()) && model.search_visible)]
+                        grab_focus: (),
 
-                            #[watch]
-                            set_active: model.default_for_domain,
+                        #[track(model.changed(BrowDiModel::search_visible()) && !model.search_visible)]
+                        set_text: "",
 
-                            connect_toggled[sender] => move |btn| {
-                                sender.input(AppInputMessage::DomainToggleToggled(btn.is_active()));
-                            },
+                        connect_search_changed[sender] => move |entry| {
+                            sender.input(AppInputMessage::SearchChanged(entry.text().into()));
                         },
 
-                        #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && model.show_keyboard_shortcuts_tooltips})]
-                        add_overlay: &model.default_for_domain_toggle_label,
+                        connect_activate => AppInputMessage::SearchActivated,
+                        connect_stop_search => AppInputMessage::SearchStopped,
+                    },
 
-                        #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && !model.show_keyboard_shortcuts_tooltips})]
-                        remove_overlay: &model.default_for_domain_toggle_label,
+                    #[local]
+                    browser_buttons_vbox -> gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
                     },
 
-                    #[wrap(Some)]
-                    set_center_widget = &gtk::Frame{
-                        set_margin_start: model.margin as i32,
-                        set_margin_end: model.margin as i32,
+                    #[local_ref]
+                    handlers_box -> gtk::Box {
+                        set_halign: gtk::Align::Center,
+                        set_visible: false,
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+                        set_spacing: model.spacing as i32,
+
+                        #[watch]
+                        set_visible: model.is_mailto && !model.webmails.is_empty(),
 
                         gtk::Label {
-                            set_margin_top: 0,
-                            set_margin_bottom: 0,
-                            set_margin_start: model.margin as i32,
-                            set_margin_end: model.margin as i32,
-                            set_justify: gtk::Justification::Center,
+                            set_label: "Browsers compose the mail in",
+                        },
+
+                        #[local_ref]
+                        webmail_dropdown -> gtk::DropDown {
+                            connect_selected_notify[sender] => move |dropdown| {
+                                sender.input(AppInputMessage::WebmailSelected(dropdown.selected()));
+                            },
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+                        set_spacing: model.spacing as i32,
+
+                        #[watch]
+                        set_visible: !model.groups.is_empty() || !model.marked.is_empty(),
+
+                        #[local_ref]
+                        groups_box -> gtk::Box {},
+
+                        gtk::Button {
+                            add_css_class: "suggested-action",
+                            connect_clicked => AppInputMessage::OpenMarked,
 
                             #[watch]
-                            set_text: &model.current_domain
-                                        .clone()
-                                        .filter(|_| !model.show_full_url)
-                                        .unwrap_or(
-                                            model.current_uri
-                                                 .clone()
-                                                 .unwrap_or(String::from(""))),
+                            set_visible: !model.marked.is_empty(),
 
                             #[watch]
-                            set_tooltip_text: model.current_uri.clone().as_deref(),
+                            set_label: &format!("Open in {} marked", model.marked.len()),
                         },
-                    },
 
-                    #[wrap(Some)]
-                    set_end_widget = &gtk::Overlay {
                         gtk::MenuButton {
-                            set_direction: gtk::ArrowType::None,
-                            set_margin_top: 0,
-                            set_margin_bottom: 0,
+                            set_label: "Save as group",
+
+                            #[watch]
+                            set_visible: model.marked.len() > 1,
+
+                            #[wrap(Some)]
+                            set_popover: group_popover = &gtk::Popover {
+                                gtk::Entry {
+                                    set_placeholder_text: Some("Name of the group"),
+
+                                    connect_activate[sender, group_popover] => move |entry| {
+                                        group_popover.popdown();
+                                        sender.input(AppInputMessage::SaveGroup(entry.text().into()));
+                                        entry.set_text("");
+                                    },
+                                },
+                            },
+                        },
+                    },
+
+                    gtk::CenterBox {
+                        set_margin_all: 0,
+
+                        #[wrap(Some)]
+                        set_start_widget: default_for_domain_toggle = &gtk::Overlay {
+                            gtk::ToggleButton {
+                                set_tooltip: "Remeber selection for the domain of the url",
+                                set_margin_all: 0,
+
+                                #[watch]
+                                set_visible: model.is_domain_toggle_visible,
+
+                                #[watch]
+                                set_active: model.default_for_domain,
+
+                                connect_toggled[sender] => move |btn| {
+                                    sender.input(AppInputMessage::DomainToggleToggled(btn.is_active()));
+                                },
+                            },
+
+                            #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && model.show_keyboard_shortcuts_tooltips})]
+                            add_overlay: &model.default_for_domain_toggle_label,
+
+                            #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && !model.show_keyboard_shortcuts_tooltips})]
+                            remove_overlay: &model.default_for_domain_toggle_label,
+                        },
+
+                        #[wrap(Some)]
+                        set_center_widget = &gtk::Frame{
                             set_margin_start: model.margin as i32,
                             set_margin_end: model.margin as i32,
 
-                            #[track(model.changed(BrowDiModel::activate_menu()) && model.activate_menu)]
-                            activate: (),
+                            gtk::Box {
+                                gtk::Label {
+                                    set_margin_top: 0,
+                                    set_margin_bottom: 0,
+                                    set_margin_start: model.margin as i32,
+                                    set_margin_end: model.margin as i32,
+                                    set_justify: gtk::Justification::Center,
+                                    set_hexpand: true,
+
+                                    #[watch]
+                                    set_visible: !model.editing_url && !model.files.is_empty(),
+
+                                    #[watch]
+                                    set_text: &model.current_domain
+                                                .clone()
+                                                .filter(|_| !model.show_full_url)
+                                                .unwrap_or(
+                                                    model.current_uri
+                                                         .clone()
+                                                         .unwrap_or(String::from(""))),
+
+                                    #[watch]
+                                    set_tooltip_text: Some(model.url_tooltip()).as_deref(),
+                                },
 
-                            #[wrap(Some)]
-                            set_popover: popover = &gtk::Popover {
-                                set_position: gtk::PositionType::Bottom,
+                                #[local_ref]
+                                launcher_entry -> gtk::Entry {
+                                    set_hexpand: true,
+                                    set_width_chars: 40,
+                                    set_placeholder_text: Some("Type, paste or drop a link"),
+
+                                    #[watch]
+                                    set_visible: model.files.is_empty() && !model.editing_url,
+
+                                    connect_changed[sender] => move |entry| {
+                                        sender.input(AppInputMessage::LauncherTextChanged(entry.text().into()));
+                                    },
+
+                                    connect_activate[sender] => move |entry| {
+                                        sender.input(AppInputMessage::LauncherUrlEntered(entry.text().into()));
+                                    },
+                                },
+
+                                gtk::Entry {
+                                    set_hexpand: true,
+                                    set_width_chars: 40,
+
+                                    #[watch]
+                                    set_visible: model.editing_url,
 
-                                connect_show => AppInputMessage::MenuOpened,
+                                    #[track(model.changed(BrowDiModel::editing_url()) && model.editing_url)]
+                                    set_text: &urls::repair(model.current_uri.as_deref().unwrap_or_default()),
 
-                                gtk::Box {
-                                    set_orientation: gtk::Orientation::Vertical,
-                                    set_spacing: 5,
-                                    gtk::Button {
-                                        set_label: "Quit",
-                                        connect_clicked => AppInputMessage::Quit
+                                    #[track(model.changed(BrowDiModel::editing_url()) && model.editing_url)]
+                                    grab_focus: (),
+
+                                    connect_activate[sender] => move |entry| {
+                                        sender.input(AppInputMessage::UrlEdited(entry.text().into()));
                                     },
-                                    gtk::ToggleButton {
-                                        set_label: "Show full url",
+                                },
+                            },
+                        },
+
+                        #[wrap(Some)]
+                        set_end_widget = &gtk::Overlay {
+                            gtk::MenuButton {
+                                set_direction: gtk::ArrowType::None,
+                                set_margin_top: 0,
+                                set_margin_bottom: 0,
+                                set_margin_start: model.margin as i32,
+                                set_margin_end: model.margin as i32,
+
+                                #[track(model.changed(BrowDiModel::activate_menu()) && model.activate_menu)]
+                                activate: (),
+
+                                #[wrap(Some)]
+                                set_popover: popover = &gtk::Popover {
+                                    set_position: gtk::PositionType::Bottom,
+
+                                    connect_show => AppInputMessage::MenuOpened,
+
+                                    gtk::Box {
+                                        set_orientation: gtk::Orientation::Vertical,
+                                        set_spacing: 5,
+                                        gtk::Button {
+                                            set_label: "Quit",
+                                            connect_clicked => AppInputMessage::Quit
+                                        },
+                                        gtk::Button {
+                                            set_label: "History",
+                                            connect_clicked[sender, popover] => move |_| {
+                                                popover.popdown();
+                                                sender.input(AppInputMessage::ShowHistory);
+                                            },
+                                        },
+                                        gtk::Button {
+                                            set_label: "Preferences",
+                                            connect_clicked[sender, popover] => move |_| {
+                                                popover.popdown();
+                                                sender.input(AppInputMessage::ShowPreferences);
+                                            },
+                                        },
+                                        gtk::Button {
+                                            set_label: "Edit url",
+
+                                            #[watch]
+                                            set_sensitive: !model.files.is_empty(),
+
+                                            connect_clicked[sender, popover] => move |_| {
+                                                popover.popdown();
+                                                sender.input(AppInputMessage::UrlEditStarted);
+                                            },
+                                        },
+                                        #[name = "copy_box"]
+                                        gtk::Box {
+                                            set_orientation: gtk::Orientation::Vertical,
+                                            set_spacing: 5,
 
-                                        #[watch]
-                                        set_active: model.show_full_url,
+                                            #[watch]
+                                            set_sensitive: model.current_uri.is_some(),
+                                        },
+                                        gtk::Button {
+                                            set_label: "Show QR code",
+                                            set_tooltip: "Ctrl+R",
+
+                                            #[watch]
+                                            set_sensitive: model.current_uri.is_some(),
+
+                                            connect_clicked[sender, popover] => move |_| {
+                                                popover.popdown();
+                                                sender.input(AppInputMessage::QrCodeToggled);
+                                            },
+                                        },
+                                        gtk::ToggleButton {
+                                            set_label: "Show full url",
+
+                                            #[watch]
+                                            set_active: model.show_full_url,
 
-                                        connect_toggled[sender] => move |btn| {
-                                            sender.input(AppInputMessage::ShowFullUrlToggleToggled(btn.is_active()));
+                                            connect_toggled[sender] => move |btn| {
+                                                sender.input(AppInputMessage::ShowFullUrlToggleToggled(btn.is_active()));
+                                            },
                                         },
                                     },
                                 },
                             },
+                            #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && model.show_keyboard_shortcuts_tooltips})]
+                            add_overlay: &model.menu_label,
+
+                            #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && !model.show_keyboard_shortcuts_tooltips})]
+                            remove_overlay: &model.menu_label,
                         },
-                        #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && model.show_keyboard_shortcuts_tooltips})]
-                        add_overlay: &model.menu_label,
+                    },
+
+                    gtk::Label {
+                        add_css_class: "dim-label",
+                        set_justify: gtk::Justification::Center,
+
+                        #[watch]
+                        set_visible: model.show_keyboard_shortcuts_tooltips && !model.modifier_actions_help.is_empty(),
 
-                        #[track({model.changed(BrowDiModel::show_keyboard_shortcuts_tooltips()) && !model.show_keyboard_shortcuts_tooltips})]
-                        remove_overlay: &model.menu_label,
+                        #[watch]
+                        set_label: &model.modifier_actions_help,
                     },
-                },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_halign: gtk::Align::Center,
+                        set_spacing: model.spacing as i32,
+
+                        #[watch]
+                        set_visible: model.rule_prompt.is_some(),
+
+                        gtk::Label {
+                            #[watch]
+                            set_label: &model.rule_prompt_text(),
+                        },
+                        gtk::Button {
+                            set_label: "Always",
+                            add_css_class: "suggested-action",
+                            connect_clicked => AppInputMessage::RulePromptAccepted,
+                        },
+                        gtk::Button {
+                            set_icon_name: "window-close-symbolic",
+                            set_tooltip: "Not now",
+                            add_css_class: "flat",
+                            connect_clicked => AppInputMessage::RulePromptDismissed,
+                        },
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_halign: gtk::Align::Center,
+                        set_spacing: model.spacing as i32,
+
+                        #[watch]
+                        set_visible: model.qr_code.is_some(),
+
+                        gtk::DrawingArea {
+                            set_content_width: 240,
+                            set_content_height: 240,
+
+                            #[track(model.changed(BrowDiModel::qr_code()))]
+                            set_draw_func: qr::draw_func(model.qr_code.clone()),
+                        },
+                        gtk::Label {
+                            add_css_class: "dim-label",
+                            set_label: "Scan to open the link on a phone",
+                        },
+                    },
+                }
             }
         }
     }
 
     fn init(
-        init: Self::Init,
+        mut init: Self::Init,
         window: Self::Root,
         sender: ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
         adw::StyleManager::default().set_color_scheme(adw::ColorScheme::ForceDark);
+        launch::remove_stale_profiles();
         let key_controller = gtk::EventControllerKey::new();
-        key_controller.connect_key_pressed(clone!(@strong sender => move |_, keyval, _keycode, _state| {
-            sender.input(AppInputMessage::KeyPressed(gtk::gdk::Key::from(keyval)));
+        key_controller.connect_key_pressed(clone!(@strong sender => move |_, keyval, keycode, state| {
+            sender.input(AppInputMessage::KeyPressed(keyval, keycode, state));
             gio::glib::Propagation::Proceed
         }));
         window.add_controller(key_controller.clone());
+        let click_controller = gtk::GestureClick::builder()
+            .button(0)
+            .propagation_phase(gtk::PropagationPhase::Capture)
+            .build();
+        click_controller.connect_pressed(clone!(@strong sender => move |_, _, _, _| {
+            sender.input(AppInputMessage::UserInteracted);
+        }));
+        window.add_controller(click_controller);
+        let launcher_entry = gtk::Entry::new();
+        let drop_target = gtk::DropTarget::new(glib::Type::INVALID, gtk::gdk::DragAction::COPY);
+        drop_target.set_types(&[gtk::gdk::FileList::static_type(), glib::Type::STRING]);
+        drop_target.connect_drop(clone!(@strong sender, @strong launcher_entry => move |_, value, _, _| {
+            // links are only taken while the launcher is shown, i.e. there is no link to open yet
+            if !launcher_entry.get_visible() {
+                return false;
+            }
+            let files: Vec<File> = match (value.get::<gtk::gdk::FileList>(), value.get::<String>()) {
+                (Ok(files), _) => files.files(),
+                (_, Ok(text)) => urls::links(&text).iter().map(|url| File::for_uri(url)).collect(),
+                _ => Vec::new(),
+            };
+            if files.is_empty() {
+                return false;
+            }
+            sender.input(AppInputMessage::LinksDropped(files));
+            true
+        }));
+        window.add_controller(drop_target);
 
         let mut browser_buttons =
             FactoryVecDeque::<BrowserButton>::builder()
                 .launch_default()
                 .forward(sender.input_sender(), |msg| match msg {
-                    BrowserButtonOutputMessage::Pressed(index) => AppInputMessage::BrowserButtonPressed(index.current_index()),
+                    BrowserButtonOutputMessage::Pressed(index, state) => AppInputMessage::BrowserButtonClicked(index.current_index(), state),
+                    BrowserButtonOutputMessage::ActionChosen(index, action) => AppInputMessage::BrowserButtonPressed(index.current_index(), action),
+                    BrowserButtonOutputMessage::ContainerChosen(index, container) => AppInputMessage::BrowserContainerChosen(index.current_index(), container),
+                    BrowserButtonOutputMessage::MiddleClicked(index) => AppInputMessage::BrowserButtonMiddleClicked(index.current_index()),
+                    BrowserButtonOutputMessage::Focused(index) => AppInputMessage::BrowserButtonFocused(index.current_index()),
                 });
 
         let browser_buttons_vbox: gtk::Box = browser_buttons.widget().clone();
-        let hotkeys = vec!['A', 'B', 'C', 'E', 'F', 'G', 'I', 'J', 'K', 'L', 'N', 'O', 'P', 'Q', 'R', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z'];
-        for browser_and_hotkey in init.browsers.iter().zip_longest(hotkeys.iter()) {
-            let hotkey: Option<char>;
-            let browser: AppInfo;
-            match browser_and_hotkey {
-                EitherOrBoth::Right(_) => break,
-                EitherOrBoth::Both(b, h) => {
-                    hotkey = Some(h.to_owned());
-                    browser = b.clone();
-                },
-                EitherOrBoth::Left(b) => {
-                    hotkey = None;
-                    browser = b.clone();
-                },
-            };
+        let settings = Settings::new("com.Nosterx.BrowDi");
+        let reserved_hotkeys = hotkeys::stored_reserved(&settings, &init.browsers);
+        let hotkeys = hotkeys::load(&settings, &init.browsers);
+        let order = suggestions::order(&settings, &init.browsers, None);
+        init.browsers.sort_by_key(|browser| order.iter().position(|id| browser.id().is_some_and(|browser_id| browser_id == *id)));
+        for browser in init.browsers.iter() {
             browser_buttons.guard().push_back(
                 BrowserButtonInit{
-                    hotkey,
+                    hotkey: browser.id().and_then(|id| hotkeys.get(id.as_str()).copied().flatten()),
+                    actions: LaunchAction::ALL.into_iter()
+                        .filter(|action| match action {
+                            LaunchAction::OpenPrivate => BrowserFamily::of(browser).is_some(),
+                            LaunchAction::OpenAsApp => BrowserFamily::of(browser).is_some_and(|family| family.app_args(None).is_some()),
+                            LaunchAction::OpenThrowaway => BrowserFamily::of(browser).is_some_and(|family| family.profile_args(std::path::Path::new("")).is_some()),
+                            _ => true,
+                        })
+                        .collect(),
+                    containers: containers::containers(browser),
                     icon: browser.icon().unwrap(),
                     name: browser.name().to_string(),
                     width: init.button_width,
@@ -396,10 +1341,33 @@ impl SimpleComponent for BrowDiModel {
             );
         }
 
-        let settings = Settings::new("com.Nosterx.BrowDi");
+        let preferences = PreferencesModel::builder()
+            .transient_for(&window)
+            .launch(PreferencesInit { browsers: init.browsers.clone(), settings: settings.clone() })
+            .forward(sender.input_sender(), |msg| match msg {
+                PreferencesOutputMessage::HotkeysChanged => AppInputMessage::HotkeysChanged,
+                PreferencesOutputMessage::ModifierActionsChanged => AppInputMessage::ModifierActionsChanged,
+                PreferencesOutputMessage::BrowserOrderChanged => AppInputMessage::BrowserOrderChanged,
+                PreferencesOutputMessage::GroupsChanged => AppInputMessage::GroupsChanged,
+            });
+        let history = HistoryModel::builder()
+            .transient_for(&window)
+            .launch(HistoryInit { browsers: init.browsers.clone(), settings: settings.clone() })
+            .forward(sender.input_sender(), |msg| match msg {
+                HistoryOutputMessage::Reopen(url, browser_id) => AppInputMessage::HistoryReopen(url, browser_id),
+            });
+        let groups_box = gtk::Box::new(gtk::Orientation::Horizontal, init.spacing.into());
+        let handlers_box = gtk::Box::new(gtk::Orientation::Horizontal, init.spacing.into());
+        let webmails = mail::webmails(&settings);
+        let webmail_dropdown = gtk::DropDown::from_strings(&webmails.iter().map(String::as_str).collect::<Vec<&str>>());
+        if let Some(position) = webmails.iter().position(|name| *name == settings.string("default-webmail")) {
+            webmail_dropdown.set_selected(position as u32);
+        }
+        let toast_overlay = adw::ToastOverlay::new();
         let menu_label = gtk::Label::builder().label("M").opacity(0.8).css_classes(vec!["background"]).build();
         let domain_label = gtk::Label::builder().label("D").opacity(0.8).css_classes(vec!["background"]).build();
-        let model = BrowDiModel {
+        let modifier_actions = hotkeys::load_modifier_actions(&settings);
+        let mut model = BrowDiModel {
             margin: init.padding,
             spacing: init.spacing,
             button_height: init.button_height,
@@ -410,67 +1378,120 @@ impl SimpleComponent for BrowDiModel {
             is_domain_toggle_visible: false,
             current_uri: None,
             current_domain: None,
+            source: None,
             settings: settings.clone(),
             show_keyboard_shortcuts_tooltips: false,
             show_full_url: settings.get("show-full-url"),
+            editing_url: false,
+            qr_code: None,
+            toasts: toast_overlay.clone(),
+            launcher_text: String::new(),
+            launcher_entry: launcher_entry.clone(),
             default_for_domain_toggle_label: domain_label,
             menu_label,
             activate_menu: false,
             tracker: 0,
             buttons: browser_buttons,
+            preferences,
+            history,
             hotkeys,
+            selected: 0,
+            search_visible: false,
+            search_query: String::new(),
+            rule_prompt: None,
+            countdown: None,
+            throwaway_sessions: 0,
+            quit_requested: false,
+            marked: Vec::new(),
+            groups: groups::load(&settings),
+            groups_box: groups_box.clone(),
+            handlers: Vec::new(),
+            handlers_box: handlers_box.clone(),
+            handoff: None,
+            is_mailto: false,
+            webmail: settings.string("default-webmail").into(),
+            webmails,
+            modifier_actions_help: hotkeys::modifier_actions_help(&modifier_actions),
+            modifier_actions,
         };
+        for (browser, key) in reserved_hotkeys {
+            let new_key = browser.id().and_then(|id| model.hotkeys.get(id.as_str()).copied().flatten());
+            let message = match new_key {
+                Some(new_key) => format!("{} is a shortcut of BrowDi now, {} got the hotkey {}", key, browser.name(), new_key),
+                None => format!("{} is a shortcut of BrowDi now, {} has no hotkey", key, browser.name()),
+            };
+            model.toasts.add_toast(adw::Toast::new(&message));
+        }
+        model.select(0, true);
+        model.reload_groups(&sender);
         let widgets = view_output!();
+        for format in CopyFormat::ALL {
+            let button = gtk::Button::builder().label(format.label()).tooltip_text(format.shortcut()).build();
+            button.connect_clicked(clone!(@strong sender, @strong widgets.popover as popover => move |_| {
+                popover.popdown();
+                sender.input(AppInputMessage::CopyRequested(format));
+            }));
+            widgets.copy_box.append(&button);
+        }
         ComponentParts { model, widgets }
     }
 
     fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         self.reset();
         match message {
-            AppInputMessage::BrowserButtonPressed(number) => {
-                if number < self.browsers.len() {
-                    if let Some(file) = self.files.pop() {
-                        let _ = self.browsers[number].launch(&[file.clone()], None::<&gio::AppLaunchContext>);
-                        if self.default_for_domain {
-                            let domain_part = file.uri().split('/').take(3).collect::<Vec<&str>>().join("/");
-                            let mut defaults: HashMap<String, Vec<String>> = self.settings.get("browsers-default-for-domains");
-                            if let Some(domains) = defaults.get_mut::<String>(&self.browsers[number].name().into()) {
-                                domains.push(domain_part);
-                            } else {
-                                defaults.insert(self.browsers[number].name().into(), vec![domain_part]);
-                            }
-                            let _ = self.settings.set("browsers-default-for-domains", defaults);
-                        }
-                        if self.files.is_empty() {
-                            sender.input(Self::Input::Quit);
-                        } else {
-                            sender.input(Self::Input::CurrentFileChanged);
-                        }
-                    } else {
-                        let _ = self.browsers[number].launch(&[], None::<&gio::AppLaunchContext>);
-                        sender.input(Self::Input::Quit);
-                    }
-                }
+            AppInputMessage::BrowserButtonPressed(number, LaunchAction::Mark) => {
+                self.toggle_mark(number);
+            }
+            AppInputMessage::BrowserButtonPressed(number, action) => {
+                self.open(number, action, DispatchOrigin::Manual, &sender);
+            }
+            AppInputMessage::BrowserContainerChosen(number, container) => {
+                let Some(browser) = self.browsers.get(number) else { return };
+                let options = LaunchOptions { container, ..self.picker_options(browser) };
+                self.open_with(number, LaunchAction::Open, options, DispatchOrigin::Manual, &sender);
+            }
+            AppInputMessage::BrowserButtonClicked(number, state) => {
+                let action = hotkeys::action_for_state(&self.modifier_actions, state);
+                sender.input(Self::Input::BrowserButtonPressed(number, action));
+            }
+            AppInputMessage::BrowserButtonMiddleClicked(number) => {
+                let action = self.modifier_actions.get(&LaunchModifier::MiddleClick).copied().unwrap_or_default();
+                sender.input(Self::Input::BrowserButtonPressed(number, action));
+            }
+            AppInputMessage::BrowserButtonFocused(number) => {
+                self.select(number, false);
             }
             AppInputMessage::DomainToggleToggled(is_toggled) => {
                 self.default_for_domain = is_toggled;
             }
-            AppInputMessage::FilesOpenRequested(files) => {
-                let defaults: HashMap<String, Vec<String>> = self.settings.get("browsers-default-for-domains");
+            AppInputMessage::FilesOpenRequested(files, source) => {
+                self.quit_requested = false;
                 let mut new_files = Vec::new();
                 for file in files.iter() {
-                    let mut found = false;
-                    if file.uri_scheme().is_some_and(|scheme| scheme == "http" || scheme == "https") {
-                        let domain_part = file.uri().split('/').take(3).collect::<Vec<&str>>().join("/");
-                        for browser in self.browsers.iter() {
-                            if defaults.get::<String>(&browser.name().into()).is_some_and(|v| v.contains(&domain_part.clone())) {
-                                let _ = browser.launch(&[file.clone()], None::<&gio::AppLaunchContext>);
-                                found = true;
-                                break;
+                    if let Some((numbers, rule_options)) = rules::matching_browsers(&self.settings, &self.browsers, file, source.as_ref()) {
+                        let mut failed = false;
+                        for number in numbers {
+                            let mut options = LaunchOptions::load(&self.settings, &self.browsers[number]);
+                            options.merge(&rule_options);
+                            match self.launch(number, std::slice::from_ref(file), &options, &sender) {
+                                Ok(()) => self.record_dispatch(number, file, DispatchOrigin::Rule),
+                                Err(error) => {
+                                    self.report_launch_error(number, &error);
+                                    failed = true;
+                                },
                             }
                         }
-                    }
-                    if !found {
+                        // the url stays to be opened by hand when the browser of the rule failed
+                        if failed {
+                            new_files.push(file.clone());
+                        }
+                    } else if let Some(handoff) = handoff::handoff(file).filter(|_| self.settings.boolean("handoff-automatically")) {
+                        let _ = handoff.app.launch_uris(&[&handoff.url], None::<&gio::AppLaunchContext>);
+                        self.record_app_dispatch(&handoff.app, file, DispatchOrigin::Rule);
+                    } else if let Some(app) = media::content_type_by_path(file).and_then(|content_type| media::automatic_handler(&self.settings, &content_type)) {
+                        let _ = app.launch(std::slice::from_ref(file), None::<&gio::AppLaunchContext>);
+                        self.record_app_dispatch(&app, file, DispatchOrigin::Rule);
+                    } else {
                         new_files.push(file.clone());
                     }
                 }
@@ -478,37 +1499,127 @@ impl SimpleComponent for BrowDiModel {
                     sender.input(Self::Input::Quit);
                 } else {
                     self.files = new_files;
+                    self.source = source;
                     sender.input(Self::Input::CurrentFileChanged);
                 }
             }
             AppInputMessage::CurrentFileChanged => {
                 if let Some(file) = self.files.last() {
                     self.current_uri = Some(file.uri().into());
-                    if file.uri_scheme().is_some_and(|scheme| scheme == "http" || scheme == "https") {
-                        self.current_domain = Some(file.uri().split('/').take(3).collect::<Vec<&str>>().join("/"));
-                        self.is_domain_toggle_visible = true;
+                    self.is_domain_toggle_visible = rules::is_web_url(file);
+                    self.current_domain = self.is_domain_toggle_visible.then(|| rules::domain_of(&file.uri()));
+                }
+                self.handoff = self.files.last().and_then(handoff::handoff);
+                self.is_mailto = self.files.last().is_some_and(mail::is_mailto);
+                let content_type = self.files.last().and_then(media::content_type_by_path);
+                self.reload_handlers(content_type.as_deref(), &sender);
+                if let Some(file) = self.files.last().filter(|file| content_type.is_none() && rules::is_web_url(file)) {
+                    if self.settings.boolean("sniff-content-types") {
+                        let url = file.uri().to_string();
+                        glib::MainContext::default().spawn_local(clone!(@strong sender => async move {
+                            let content_type = media::sniff(url.clone()).await;
+                            sender.input(AppInputMessage::ContentTypeDetected(url, content_type));
+                        }));
                     }
                 }
+                self.rule_prompt = None;
+                if BrowserOrder::load(&self.settings) == BrowserOrder::DomainFrecency {
+                    self.apply_order();
+                }
+                let mut suggested = None;
+                let running = RunningProcesses::scan();
+                let running: Vec<bool> = self.browsers.iter().map(|browser| running.is_running(browser)).collect();
+                for (number, running) in running.iter().enumerate() {
+                    self.buttons.send(number, BrowserButtonInputMessage::SetRunning(*running));
+                }
+                if let Some(domain) = self.current_domain.clone() {
+                    let choices = suggestions::load(&self.settings);
+                    suggested = suggestions::suggested_browser(&choices, &domain).and_then(|id| self.browser_with_id(&id));
+                    let times = self.settings.uint("rule-prompt-after-choices") as usize;
+                    self.rule_prompt = suggestions::consistent_browser(&choices, &domain, times).and_then(|id| self.browser_with_id(&id));
+                }
+                if suggested.is_none() && self.settings.boolean("prefer-running-browsers") {
+                    suggested = running.iter().position(|running| *running);
+                }
+                if let Some(number) = suggested {
+                    self.select(number, true);
+                }
+                if !self.files.is_empty() {
+                    self.start_countdown(suggested, &sender);
+                }
             }
-            AppInputMessage::KeyPressed(key) => {
+            AppInputMessage::KeyPressed(key, keycode, state) => {
+                self.cancel_countdown();
+                let action = hotkeys::action_for_state(&self.modifier_actions, state);
                 match key {
-                    gtk::gdk::Key::q => sender.input(Self::Input::Quit),
-                    gtk::gdk::Key::s => sender.input(Self::Input::ShowFullUrlToggleToggled(!self.show_full_url)),
-                    gtk::gdk::Key::h => {
+                    gtk::gdk::Key::Escape if self.search_visible => return sender.input(Self::Input::SearchStopped),
+                    gtk::gdk::Key::Escape if self.editing_url => return sender.input(Self::Input::UrlEditCancelled),
+                    gtk::gdk::Key::Escape if self.qr_code.is_some() => return sender.input(Self::Input::QrCodeToggled),
+                    gtk::gdk::Key::Escape if !self.marked.is_empty() => return self.clear_marks(),
+                    gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter if !self.marked.is_empty() => return sender.input(Self::Input::OpenMarked),
+                    gtk::gdk::Key::Escape => return sender.input(Self::Input::Quit),
+                    gtk::gdk::Key::Return | gtk::gdk::Key::KP_Enter => return sender.input(Self::Input::BrowserButtonPressed(self.selected, action)),
+                    gtk::gdk::Key::space if state.contains(gtk::gdk::ModifierType::CONTROL_MASK) => return self.toggle_mark(self.selected),
+                    gtk::gdk::Key::Menu => return self.buttons.send(self.selected, BrowserButtonInputMessage::ShowMenu),
+                    gtk::gdk::Key::F10 if state.contains(gtk::gdk::ModifierType::SHIFT_MASK) => return self.buttons.send(self.selected, BrowserButtonInputMessage::ShowMenu),
+                    _ => {},
+                }
+                let Some(key) = hotkeys::latin_key(key, keycode) else { return };
+                if state.contains(gtk::gdk::ModifierType::CONTROL_MASK) {
+                    let shift = state.contains(gtk::gdk::ModifierType::SHIFT_MASK);
+                    match (key.to_ascii_lowercase(), shift) {
+                        ('c', false) => return sender.input(Self::Input::CopyRequested(CopyFormat::Url)),
+                        ('c', true) => return sender.input(Self::Input::CopyRequested(CopyFormat::CleanUrl)),
+                        ('k', false) => return sender.input(Self::Input::CopyRequested(CopyFormat::Markdown)),
+                        ('k', true) => return sender.input(Self::Input::CopyRequested(CopyFormat::Html)),
+                        ('r', _) => return sender.input(Self::Input::QrCodeToggled),
+                        ('l', false) if self.files.is_empty() => {
+                            self.launcher_entry.grab_focus();
+                            return;
+                        },
+                        ('v', false) if self.files.is_empty() => {
+                            let Some(display) = gtk::gdk::Display::default() else { return };
+                            display.clipboard().read_text_async(None::<&gio::Cancellable>, clone!(@strong sender => move |text| {
+                                let Ok(Some(text)) = text else { return };
+                                // several lines are a list of links as when dropped, one line may be a search
+                                match text.trim().contains('\n') {
+                                    true => {
+                                        let files = urls::links(&text).iter().map(|url| File::for_uri(url)).collect();
+                                        sender.input(AppInputMessage::LinksDropped(files));
+                                    },
+                                    false => sender.input(AppInputMessage::LauncherUrlEntered(text.into())),
+                                }
+                            }));
+                            return;
+                        },
+                        _ => {},
+                    }
+                }
+                match key.to_ascii_lowercase() {
+                    'q' => sender.input(Self::Input::Quit),
+                    's' => sender.input(Self::Input::ShowFullUrlToggleToggled(!self.show_full_url)),
+                    'e' => sender.input(Self::Input::UrlEditStarted),
+                    'h' => {
                         self.set_show_keyboard_shortcuts_tooltips(!self.show_keyboard_shortcuts_tooltips);
                         *APP_STATE.write() = self.show_keyboard_shortcuts_tooltips;
                     },
-                    gtk::gdk::Key::d => {
+                    'd' => {
                         self.default_for_domain = !self.default_for_domain;
                     },
-                    gtk::gdk::Key::m => {
+                    'm' => {
                         self.set_activate_menu(!self.activate_menu)
                     },
-                    key => {
-                        if let Some(key_upper) = key.to_upper().to_unicode() {
-                            if let Some(key_num) = self.hotkeys.iter().position(|k| k == &key_upper) {
-                                if key_num < self.browsers.len() {
-                                    sender.input(Self::Input::BrowserButtonPressed(key_num));
+                    '/' => {
+                        self.set_search_visible(true);
+                    },
+                    _ => {
+                        // Shift turns digits into symbols like `!`, the key itself is the hotkey
+                        if let Some(hotkey) = hotkeys::normalize(key).or_else(|| hotkeys::base_key(keycode)) {
+                            if let Some(number) = self.browser_with_hotkey(hotkey) {
+                                sender.input(Self::Input::BrowserButtonPressed(number, action));
+                            } else if let Some(digit @ 1..=9) = hotkey.to_digit(10) {
+                                if let Some(number) = self.visible_browsers().get(digit as usize - 1) {
+                                    sender.input(Self::Input::BrowserButtonPressed(*number, action));
                                 }
                             }
                         }
@@ -516,15 +1627,197 @@ impl SimpleComponent for BrowDiModel {
                 }
             },
             AppInputMessage::Quit => {
-                relm4::main_application().quit();
+                if self.throwaway_sessions > 0 {
+                    self.quit_requested = true;
+                } else {
+                    relm4::main_application().quit();
+                }
+            }
+            AppInputMessage::OpenMarked => {
+                let marked = self.marked.clone();
+                self.open_many(marked, DispatchOrigin::Manual, &sender);
+            }
+            AppInputMessage::OpenGroup(number) => {
+                let Some((name, _)) = self.groups.get(number) else { return };
+                let numbers = groups::resolve(&self.settings, &self.browsers, &groups::target(name));
+                self.open_many(numbers, DispatchOrigin::Manual, &sender);
+            }
+            AppInputMessage::SaveGroup(name) => {
+                let name = name.trim();
+                if name.is_empty() || self.marked.is_empty() {
+                    return;
+                }
+                let ids = self.marked.iter().filter_map(|number| self.browsers[*number].id()).map(String::from).collect();
+                groups::save(&self.settings, name, ids);
+                sender.input(Self::Input::GroupsChanged);
+            }
+            AppInputMessage::GroupsChanged => {
+                self.groups = groups::load(&self.settings);
+                self.reload_groups(&sender);
+            }
+            AppInputMessage::ThrowawaySessionEnded => {
+                self.throwaway_sessions -= 1;
+                if self.throwaway_sessions == 0 && self.quit_requested {
+                    relm4::main_application().quit();
+                }
             }
             AppInputMessage::ShowFullUrlToggleToggled(is_toggled) => {
                 self.show_full_url = is_toggled;
                 let _ = self.settings.set("show-full-url", is_toggled);
             }
+            AppInputMessage::UrlEditStarted => {
+                if !self.files.is_empty() {
+                    self.cancel_countdown();
+                    self.set_editing_url(true);
+                }
+            }
+            AppInputMessage::UrlEdited(text) => {
+                self.set_editing_url(false);
+                let url = urls::repair(&text);
+                if url.is_empty() || self.current_uri.as_ref() == Some(&url) {
+                    return;
+                }
+                // the edited url goes through rules again as if it was opened with BrowDi
+                let mut files = self.files.clone();
+                if let Some(file) = files.last_mut() {
+                    *file = File::for_commandline_arg(&url);
+                }
+                sender.input(Self::Input::FilesOpenRequested(files, self.source.clone()));
+            }
+            AppInputMessage::UrlEditCancelled => {
+                self.set_editing_url(false);
+            }
+            AppInputMessage::CopyRequested(format) => {
+                let Some(url) = self.current_uri.clone() else { return };
+                let title = self.current_domain.clone().unwrap_or(url.clone());
+                let Some(display) = gtk::gdk::Display::default() else { return };
+                display.clipboard().set_text(&format.text(&url, &title));
+                self.toasts.add_toast(adw::Toast::new(match format {
+                    CopyFormat::Url => "Url copied",
+                    CopyFormat::CleanUrl => "Url without tracking copied",
+                    CopyFormat::Markdown => "Markdown link copied",
+                    CopyFormat::Html => "HTML link copied",
+                }));
+            }
+            AppInputMessage::LauncherTextChanged(text) => {
+                self.cancel_countdown();
+                self.launcher_text = text;
+            }
+            AppInputMessage::LauncherUrlEntered(text) => {
+                if text.trim().is_empty() {
+                    return sender.input(Self::Input::BrowserButtonPressed(self.selected, LaunchAction::Open));
+                }
+                match queries::to_url(&self.settings, &text) {
+                    Some(url) => sender.input(Self::Input::FilesOpenRequested(vec![File::for_uri(&url)], None)),
+                    None => self.toasts.add_toast(adw::Toast::new("Not a link")),
+                }
+            }
+            AppInputMessage::ContentTypeDetected(url, content_type) => {
+                let Some(content_type) = content_type.filter(|_| self.current_uri.as_ref() == Some(&url)) else { return };
+                match media::automatic_handler(&self.settings, &content_type) {
+                    Some(app) => self.open_in_app(&app, None, DispatchOrigin::Rule, &sender),
+                    None => self.reload_handlers(Some(&content_type), &sender),
+                }
+            }
+            AppInputMessage::HandlerChosen(number) => {
+                let Some(app) = self.handlers.get(number).cloned() else { return };
+                self.open_in_app(&app, None, DispatchOrigin::Manual, &sender);
+            }
+            AppInputMessage::WebmailSelected(position) => {
+                self.cancel_countdown();
+                if let Some(webmail) = self.webmails.get(position as usize) {
+                    self.webmail = webmail.clone();
+                }
+            }
+            AppInputMessage::HandoffChosen => {
+                let Some(handoff) = self.handoff.clone() else { return };
+                self.open_in_app(&handoff.app, Some(&handoff.url), DispatchOrigin::Manual, &sender);
+            }
+            AppInputMessage::LinksDropped(files) => {
+                if files.is_empty() {
+                    self.toasts.add_toast(adw::Toast::new("Not a link"));
+                } else if self.files.is_empty() {
+                    sender.input(Self::Input::FilesOpenRequested(files, None));
+                }
+            }
+            AppInputMessage::QrCodeToggled => {
+                self.cancel_countdown();
+                let qr_code = match self.qr_code {
+                    Some(_) => None,
+                    None => self.current_uri.clone(),
+                };
+                self.set_qr_code(qr_code);
+            }
             AppInputMessage::MenuOpened => {
                 self.set_activate_menu(false)
             }
+            AppInputMessage::ShowPreferences => {
+                self.preferences.emit(PreferencesInputMessage::Show);
+            }
+            AppInputMessage::HotkeysChanged => {
+                self.hotkeys = hotkeys::load(&self.settings, &self.browsers);
+                for (number, browser) in self.browsers.iter().enumerate() {
+                    let hotkey = browser.id().and_then(|id| self.hotkeys.get(id.as_str()).copied().flatten());
+                    self.buttons.send(number, BrowserButtonInputMessage::SetHotkey(hotkey));
+                }
+            }
+            AppInputMessage::SearchChanged(query) => {
+                self.search_query = query;
+                self.apply_search();
+            }
+            AppInputMessage::SearchActivated => {
+                if self.selected < self.browsers.len() && self.search_score(self.selected).is_some() {
+                    sender.input(Self::Input::BrowserButtonPressed(self.selected, LaunchAction::Open));
+                }
+            }
+            AppInputMessage::SearchStopped => {
+                self.set_search_visible(false);
+                self.search_query.clear();
+                self.apply_search();
+                self.select(self.selected, true);
+            }
+            AppInputMessage::ShowHistory => {
+                self.history.emit(HistoryInputMessage::Show);
+            }
+            AppInputMessage::HistoryReopen(url, browser_id) => {
+                if let Some(number) = self.browser_with_id(&browser_id) {
+                    let file = File::for_uri(&url);
+                    let options = LaunchOptions::load(&self.settings, &self.browsers[number]);
+                    match self.launch(number, std::slice::from_ref(&file), &options, &sender) {
+                        Ok(()) => self.record_dispatch(number, &file, DispatchOrigin::Manual),
+                        Err(error) => self.report_launch_error(number, &error),
+                    }
+                }
+            }
+            AppInputMessage::RulePromptAccepted => {
+                if let Some(number) = self.rule_prompt.take() {
+                    sender.input(Self::Input::BrowserButtonPressed(number, LaunchAction::OpenAndRemember));
+                }
+            }
+            AppInputMessage::RulePromptDismissed => {
+                self.rule_prompt = None;
+            }
+            AppInputMessage::BrowserOrderChanged => {
+                self.apply_order();
+            }
+            AppInputMessage::CountdownTick => {
+                let Some(countdown) = &self.countdown else { return };
+                let elapsed = countdown.started.elapsed();
+                if elapsed >= countdown.duration {
+                    let target = countdown.target;
+                    self.open(target, LaunchAction::Open, DispatchOrigin::Countdown, &sender);
+                } else {
+                    let remaining = 1.0 - elapsed.as_secs_f64() / countdown.duration.as_secs_f64();
+                    self.buttons.send(countdown.target, BrowserButtonInputMessage::SetProgress(Some(remaining)));
+                }
+            }
+            AppInputMessage::UserInteracted => {
+                self.cancel_countdown();
+            }
+            AppInputMessage::ModifierActionsChanged => {
+                self.modifier_actions = hotkeys::load_modifier_actions(&self.settings);
+                self.modifier_actions_help = hotkeys::modifier_actions_help(&self.modifier_actions);
+            }
         }
     }
 
@@ -540,13 +1833,48 @@ fn main() {
 
     let sender = BASE_BROKER.sender();
 
+    gtk_app.add_main_option(
+        "source",
+        gtk::glib::Char::from(0),
+        gtk::glib::OptionFlags::NONE,
+        gtk::glib::OptionArg::String,
+        "Name of the application the links come from, used by rules",
+        Some("NAME"),
+    );
+
+    // The primary instance learns the source of links opened by another instance from the hint.
+    // Arguments that are not links, e.g. search queries, are expanded before GApplication turns them into paths.
+    let local_source: Rc<RefCell<Option<Source>>> = Rc::default();
+    let local_files: Rc<RefCell<Option<Vec<File>>>> = Rc::default();
+    gtk_app.connect_handle_local_options(clone!(@strong local_source, @strong local_files => move |application, options| {
+        let args = sources::file_arguments(std::env::args_os());
+        if args.is_empty() {
+            return -1;
+        }
+        let settings = Settings::new("com.Nosterx.BrowDi");
+        let files: Vec<File> = args.iter().map(|arg| queries::file_for_argument(&settings, arg)).collect();
+        let source = options.lookup::<String>("source").ok().flatten()
+            .map(|name| Source::named(&name))
+            .or_else(sources::detect);
+        if application.register(None::<&gio::Cancellable>).is_ok() && application.is_remote() {
+            application.open(&files, &source.map(|source| source.to_hint()).unwrap_or_default());
+            return 0;
+        }
+        *local_source.borrow_mut() = source;
+        *local_files.borrow_mut() = Some(files);
+        -1
+    }));
+
     gtk_app.connect_open(
-        gtk::glib::clone!(@strong sender => move |application, files, _hint| {
-            sender.send(AppInputMessage::FilesOpenRequested(files.to_vec())).unwrap();
+        gtk::glib::clone!(@strong sender, @strong local_source, @strong local_files => move |application, files, hint| {
+            let source = Source::from_hint(hint).or_else(|| local_source.borrow_mut().take());
+            let files = local_files.borrow_mut().take().unwrap_or_else(|| files.to_vec());
+            sender.send(AppInputMessage::FilesOpenRequested(files, source)).unwrap();
             application.activate();
         }),
     );
 
     let app = RelmApp::from_app(gtk_app).with_broker(&BASE_BROKER);
+    app.set_global_css(STYLE);
     app.run::<BrowDiModel>(BrowDiInit::default());
 }
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use gio::{glib, AppInfo, File, Settings};
+use itertools::Itertools;
+
+use crate::mail;
+
+
+/// Content types of documents, other types than documents, video and audio are shown fine by browsers.
+const DOCUMENT_TYPES: [&str; 7] = [
+    "application/pdf", "application/epub+zip", "application/postscript", "image/vnd.djvu",
+    "application/msword", "application/vnd.ms-excel", "application/vnd.ms-powerpoint",
+];
+
+/// Prefixes of content types of office documents.
+const DOCUMENT_PREFIXES: [&str; 2] = ["application/vnd.oasis.opendocument.", "application/vnd.openxmlformats-officedocument."];
+
+/// How many applications are offered for a link besides browsers.
+const MAX_HANDLERS: usize = 3;
+
+const MAX_REDIRECTS: usize = 3;
+
+/// Seconds to wait for a server to answer a HEAD request.
+const SNIFF_TIMEOUT: u32 = 3;
+
+/// Longest response head read from a server.
+const MAX_HEAD_LENGTH: usize = 16 * 1024;
+
+
+/// Checks if `path` on `host` is the page of a single video that players like mpv open by url,
+/// not a channel, a search or the home page of the video host.
+fn is_video_page(host: &str, path: &str, query: Option<&str>) -> bool {
+    let host = host.strip_prefix("www.").or_else(|| host.strip_prefix("m.")).unwrap_or(host);
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+    let is_number = |segment: &str| segment.chars().all(|c| c.is_ascii_digit());
+    match (host, segments.as_slice()) {
+        ("youtube.com" | "music.youtube.com", ["watch"]) => {
+            query.is_some_and(|query| query.split('&').any(|parameter| parameter.len() > 2 && parameter.starts_with("v=")))
+        },
+        ("youtube.com", ["shorts" | "live", _]) | ("youtu.be", [_]) | ("dailymotion.com", ["video", _]) => true,
+        ("vimeo.com", [id]) | ("twitch.tv", ["videos", id]) => is_number(id),
+        _ => false,
+    }
+}
+
+
+fn is_media(content_type: &str) -> bool {
+    content_type.starts_with("video/")
+        || content_type.starts_with("audio/")
+        || DOCUMENT_TYPES.contains(&content_type)
+        || DOCUMENT_PREFIXES.iter().any(|prefix| content_type.starts_with(prefix))
+}
+
+
+/// Content type of a media file or a document `file` points at guessed from the extension of its path,
+/// e.g. `video/mp4` for `.mp4`. Pages of video hosts count as videos and `mailto:` links have
+/// the type mail clients are registered for.
+pub fn content_type_by_path(file: &File) -> Option<String> {
+    if mail::is_mailto(file) {
+        return Some(String::from(mail::MAILTO_TYPE));
+    }
+    let uri = glib::Uri::parse(&file.uri(), glib::UriFlags::ENCODED).ok()?;
+    let path = uri.path();
+    let host = uri.host().map(|host| host.to_lowercase()).unwrap_or_default();
+    if is_video_page(&host, &path, uri.query().as_deref()) {
+        return Some(String::from("video/mp4"));
+    }
+    let name = path.rsplit('/').next().filter(|name| name.contains('.'))?;
+    let (content_type, _) = gio::content_type_guess(Some(name), &[]);
+    let content_type = gio::content_type_get_mime_type(&content_type).map(String::from)?;
+    is_media(&content_type).then_some(content_type)
+}
+
+
+/// Reads the status line and headers of a response.
+async fn read_head(stream: gio::InputStream) -> Option<String> {
+    let mut head = Vec::new();
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_HEAD_LENGTH {
+        let (buffer, read) = stream.read_future(vec![0; 1024], glib::Priority::DEFAULT).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Some(String::from_utf8_lossy(&head).into_owned())
+}
+
+
+/// Asks the server for the content type of `url` with a HEAD request, following a few redirects.
+/// Returns `None` if it is not a media file or a document.
+pub async fn sniff(url: String) -> Option<String> {
+    let client = gio::SocketClient::new();
+    client.set_timeout(SNIFF_TIMEOUT);
+    let mut url = url;
+    for _ in 0..=MAX_REDIRECTS {
+        let uri = glib::Uri::parse(&url, glib::UriFlags::ENCODED).ok()?;
+        let tls = match uri.scheme().as_str() {
+            "https" => true,
+            "http" => false,
+            _ => return None,
+        };
+        client.set_tls(tls);
+        let connection = client.connect_to_uri_future(&url, if tls { 443 } else { 80 }).await.ok()?;
+        let host = match uri.port() {
+            -1 => uri.host()?.to_string(),
+            port => format!("{}:{}", uri.host()?, port),
+        };
+        let target = match uri.query() {
+            Some(query) => format!("{}?{}", uri.path(), query),
+            None => uri.path().to_string(),
+        };
+        let target = if target.is_empty() { String::from("/") } else { target };
+        let request = format!("HEAD {target} HTTP/1.1\r\nHost: {host}\r\nUser-Agent: BrowDi\r\nAccept: */*\r\nConnection: close\r\n\r\n");
+        connection.output_stream().write_all_future(request.into_bytes(), glib::Priority::DEFAULT).await.ok()?;
+        let head = read_head(connection.input_stream()).await?;
+        let _ = connection.close_future(glib::Priority::DEFAULT).await;
+
+        let mut lines = head.lines();
+        let status: u32 = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+        let headers: HashMap<String, String> = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+            .collect();
+        match status {
+            301 | 302 | 303 | 307 | 308 => {
+                url = glib::Uri::resolve_relative(Some(&url), headers.get("location")?, glib::UriFlags::ENCODED).ok()?.into();
+            }
+            200..=299 => {
+                let content_type = headers.get("content-type")?.split(';').next()?.trim().to_lowercase();
+                return is_media(&content_type).then_some(content_type);
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+
+/// Applications recommended for `content_type` that open urls, browsers are left out as they
+/// have buttons of their own and so is BrowDi.
+pub fn handlers(content_type: &str, browsers: &[AppInfo]) -> Vec<AppInfo> {
+    AppInfo::recommended_for_type(content_type)
+        .into_iter()
+        .filter(|app| app.should_show() && app.supports_uris())
+        .filter(|app| !browsers.iter().any(|browser| browser.id() == app.id()))
+        .filter(|app| !app.id().is_some_and(|id| id.starts_with("browdi")))
+        .unique_by(|app| app.id())
+        .take(MAX_HANDLERS)
+        .collect()
+}
+
+
+/// Application configured to open links of `content_type` automatically, patterns like `video/*`
+/// match every subtype.
+pub fn automatic_handler(settings: &Settings, content_type: &str) -> Option<AppInfo> {
+    let handlers: HashMap<String, String> = settings.get("handlers-for-types");
+    let (_, id) = handlers.iter()
+        .filter(|(pattern, _)| match pattern.strip_suffix('*') {
+            Some(prefix) => content_type.starts_with(prefix),
+            None => content_type == pattern.as_str(),
+        })
+        .max_by_key(|(pattern, _)| pattern.len())?;
+    AppInfo::all().into_iter().find(|app| app.id().is_some_and(|app_id| app_id == id.as_str()))
+}
+
+
+/// Formats automatic handlers as `video/*=mpv.desktop, application/pdf=org.gnome.Evince.desktop`.
+pub fn format_handlers(handlers: &HashMap<String, String>) -> String {
+    let mut handlers: Vec<String> = handlers.iter().map(|(pattern, id)| format!("{pattern}={id}")).collect();
+    handlers.sort();
+    handlers.join(", ")
+}
+
+
+/// Parses automatic handlers like `video/*=mpv.desktop`, returns `None` if any of them is invalid.
+pub fn parse_handlers(text: &str) -> Option<HashMap<String, String>> {
+    text.split(',')
+        .filter(|item| !item.trim().is_empty())
+        .map(|item| {
+            let (pattern, id) = item.split_once('=')?;
+            let (pattern, id) = (pattern.trim(), id.trim());
+            (pattern.contains('/') && !id.is_empty()).then(|| (pattern.to_string(), id.to_string()))
+        })
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    fn content_type(url: &str) -> Option<String> {
+        content_type_by_path(&File::for_uri(url))
+    }
+
+    #[test]
+    fn finds_video_pages() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+            "https://m.youtube.com/watch?list=PL1&v=dQw4w9WgXcQ",
+            "https://youtube.com/shorts/abc123",
+            "https://youtu.be/dQw4w9WgXcQ?si=x",
+            "https://vimeo.com/76979871",
+            "https://www.twitch.tv/videos/123456789",
+            "https://www.dailymotion.com/video/x8abcd",
+        ] {
+            assert_eq!(content_type(url).as_deref(), Some("video/mp4"), "{url}");
+        }
+        for url in [
+            "https://www.youtube.com/",
+            "https://www.youtube.com/@rustlang",
+            "https://www.youtube.com/results?search_query=rust",
+            "https://www.youtube.com/watch",
+            "https://youtu.be/",
+            "https://vimeo.com/channels/staffpicks",
+            "https://www.twitch.tv/rustlang",
+            "https://www.dailymotion.com/us",
+        ] {
+            assert_eq!(content_type(url), None, "{url}");
+        }
+    }
+
+    #[test]
+    fn guesses_documents_by_extension() {
+        assert_eq!(content_type("https://example.com/papers/paper.pdf?download=1").as_deref(), Some("application/pdf"));
+        assert_eq!(content_type("https://example.com/clip.webm").as_deref(), Some("video/webm"));
+        assert_eq!(content_type("https://example.com/index.html"), None);
+        assert_eq!(content_type("https://example.com/docs/"), None);
+    }
+
+    /// Serves `responses` one per connection and returns the requests it got.
+    fn serve(responses: Vec<&'static str>) -> (u16, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            responses.into_iter()
+                .map(|response| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut request = Vec::new();
+                    let mut buffer = [0; 1024];
+                    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                        let read = stream.read(&mut buffer).unwrap();
+                        request.extend_from_slice(&buffer[..read]);
+                    }
+                    stream.write_all(response.as_bytes()).unwrap();
+                    String::from_utf8(request).unwrap()
+                })
+                .collect()
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn sniffs_content_type_after_redirects() {
+        let (port, server) = serve(vec![
+            "HTTP/1.1 302 Found\r\nLocation: /files/report\r\nContent-Length: 0\r\n\r\n",
+            "HTTP/1.1 200 OK\r\nContent-Type: application/pdf; qs=0.9\r\nContent-Length: 1024\r\n\r\n",
+        ]);
+        let content_type = glib::MainContext::new().block_on(sniff(format!("http://127.0.0.1:{port}/download?id=7")));
+        assert_eq!(content_type.as_deref(), Some("application/pdf"));
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("HEAD /download?id=7 HTTP/1.1\r\n"));
+        assert!(requests[0].contains(&format!("\r\nHost: 127.0.0.1:{port}\r\n")));
+        assert!(requests[1].starts_with("HEAD /files/report HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn sniffs_pages_as_no_media() {
+        let (port, server) = serve(vec!["HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n"]);
+        assert_eq!(glib::MainContext::new().block_on(sniff(format!("http://127.0.0.1:{port}/"))), None);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,1225 @@
+use std::collections::HashMap;
+
+use gio::{AppInfo, Settings};
+use gio::prelude::AppInfoExt;
+use gtk::glib::{self, clone};
+use itertools::Itertools;
+use relm4::{adw, gtk, ComponentParts, ComponentSender, RelmWidgetExt, SimpleComponent};
+use relm4::adw::prelude::*;
+
+use crate::groups;
+use crate::hotkeys::{self, HotkeyConflict, LaunchAction, LaunchModifier};
+use crate::launch::{self, LaunchOptions};
+use crate::mail;
+use crate::media;
+use crate::queries;
+use crate::rules::{self, Rule};
+use crate::sources;
+use crate::suggestions::BrowserOrder;
+
+
+const DAY_LABELS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+
+pub struct PreferencesInit {
+    pub browsers: Vec<AppInfo>,
+    pub settings: Settings,
+}
+
+
+pub struct PreferencesModel {
+    window: adw::PreferencesWindow,
+    browsers: Vec<AppInfo>,
+    settings: Settings,
+    hotkeys: HashMap<String, Option<char>>,
+    hotkey_entries: HashMap<String, gtk::Entry>,
+    rules: Vec<Rule>,
+    rules_group: adw::PreferencesGroup,
+    rule_rows: Vec<adw::ExpanderRow>,
+    groups: Vec<(String, Vec<String>)>,
+    groups_group: adw::PreferencesGroup,
+    group_rows: Vec<adw::ActionRow>,
+}
+
+
+#[derive(Debug)]
+pub enum PreferencesInputMessage {
+    Show,
+    HotkeyEdited(String, String),
+    ModifierActionSelected(LaunchModifier, u32),
+    BrowserOrderSelected(u32),
+    CountdownFallbackSelected(u32),
+    CountdownOverridesEdited(gtk::Entry),
+    HandlersEdited(gtk::Entry),
+    SearchEnginesEdited(gtk::TextView),
+    UrlTemplatesEdited(gtk::TextView),
+    WebmailTemplatesEdited(gtk::TextView),
+    GroupRemoved(usize),
+    RuleAdded,
+    RuleRemoved(usize),
+    RuleMovedUp(usize),
+    RuleDomainEdited(usize, String),
+    RuleSourceEdited(usize, String),
+    RuleTargetSelected(usize, u32),
+    RuleDayToggled(usize, u32, bool),
+    RuleRunningToggled(usize, bool),
+    RuleMailToggled(usize, bool),
+    RulePrivateToggled(usize, bool),
+    RuleAppModeToggled(usize, bool),
+    RuleThrowawayToggled(usize, bool),
+    RuleContainerEdited(usize, String),
+    RuleWebmailEdited(usize, String),
+    RuleArgsEdited(usize, gtk::Entry),
+    RuleWrapperEdited(usize, gtk::Entry),
+    RuleEnvEdited(usize, gtk::Entry),
+    BrowserArgsEdited(usize, gtk::Entry),
+    BrowserEnvEdited(usize, gtk::Entry),
+    BrowserWrapperEdited(usize, gtk::Entry),
+    RuleTimesEdited(usize, gtk::Entry),
+    RuleDatesEdited(usize, gtk::Entry),
+}
+
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum PreferencesOutputMessage {
+    HotkeysChanged,
+    ModifierActionsChanged,
+    BrowserOrderChanged,
+    GroupsChanged,
+}
+
+
+impl PreferencesModel {
+    fn browser_name(&self, browser_id: &str) -> String {
+        if let Some(name) = groups::group_name(browser_id) {
+            return format!("{} group", name);
+        }
+        self.browsers.iter()
+            .find(|browser| browser.id().is_some_and(|id| id == browser_id))
+            .map(|browser| browser.name().to_string())
+            .unwrap_or(browser_id.to_string())
+    }
+
+    /// Targets of rules as ids and names, browsers first and then groups.
+    fn targets(&self) -> Vec<(String, String)> {
+        let browsers = self.browsers.iter()
+            .filter_map(|browser| Some((browser.id()?.to_string(), browser.name().to_string())));
+        let groups = self.groups.iter().map(|(name, _)| (groups::target(name), format!("{} group", name)));
+        browsers.chain(groups).collect()
+    }
+
+    fn reload_group_rows(&mut self, sender: &ComponentSender<Self>) {
+        for row in self.group_rows.drain(..) {
+            self.groups_group.remove(&row);
+        }
+        for (number, (name, members)) in self.groups.iter().enumerate() {
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(name))
+                .subtitle(glib::markup_escape_text(&members.iter().map(|id| self.browser_name(id)).join(", ")))
+                .build();
+            let remove_button = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Remove group")
+                .valign(gtk::Align::Center)
+                .css_classes(vec!["flat"])
+                .build();
+            remove_button.connect_clicked(clone!(@strong sender => move |_| {
+                sender.input(PreferencesInputMessage::GroupRemoved(number));
+            }));
+            row.add_suffix(&remove_button);
+            self.groups_group.add(&row);
+            self.group_rows.push(row);
+        }
+    }
+
+    fn rule_summary(&self, rule: &Rule) -> String {
+        if rule.schedule.is_always() && !rule.running && !rule.mail && rule.source.is_empty() && rule.launch.container.is_empty() && rule.launch.webmail.is_empty() {
+            return glib::markup_escape_text(&format!("{}, always", self.browser_name(&rule.target))).into();
+        }
+        let mut conditions = Vec::new();
+        if rule.mail {
+            conditions.push(String::from("mail links"));
+        }
+        if !rule.source.is_empty() {
+            conditions.push(format!("from {}", rule.source));
+        }
+        if !rule.launch.container.is_empty() {
+            conditions.push(format!("in the {} container", rule.launch.container));
+        }
+        if !rule.launch.webmail.is_empty() {
+            conditions.push(format!("mail in {}", rule.launch.webmail));
+        }
+        if rule.running {
+            conditions.push(String::from("while running"));
+        }
+        if !rule.schedule.days.is_empty() {
+            conditions.push(rule.schedule.days.iter().filter_map(|day| (*day as usize).checked_sub(1).and_then(|index| DAY_LABELS.get(index))).join(" "));
+        }
+        if !rule.schedule.times.is_empty() {
+            conditions.push(rule.schedule.format_times());
+        }
+        if !rule.schedule.dates.is_empty() {
+            conditions.push(rule.schedule.format_dates());
+        }
+        glib::markup_escape_text(&format!("{}, {}", self.browser_name(&rule.target), conditions.join(", "))).into()
+    }
+
+    fn update_rule_row(&self, number: usize) {
+        let (Some(rule), Some(row)) = (self.rules.get(number), self.rule_rows.get(number)) else { return };
+        let links = if rule.mail { "mail link" } else { "link" };
+        let title = match (rule.domain.is_empty(), rule.source.is_empty()) {
+            (false, _) => rule.domain.clone(),
+            (true, false) => format!("Any {} from {}", links, rule.source),
+            (true, true) if rule.mail => String::from("Any mail link"),
+            (true, true) => String::from("New rule"),
+        };
+        row.set_title(&glib::markup_escape_text(&title));
+        row.set_subtitle(&self.rule_summary(rule));
+    }
+
+    fn rule_row(&self, number: usize, rule: &Rule, sender: &ComponentSender<Self>) -> adw::ExpanderRow {
+        let row = adw::ExpanderRow::new();
+
+        let domain_entry = gtk::Entry::builder()
+            .text(&rule.domain)
+            .placeholder_text("example.com")
+            .valign(gtk::Align::Center)
+            .build();
+        domain_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleDomainEdited(number, entry.text().into()));
+        }));
+        let domain_row = adw::ActionRow::builder().title("Domain").subtitle("Subdomains are matched too").build();
+        domain_row.add_suffix(&domain_entry);
+        row.add_row(&domain_row);
+
+        let mail_switch = gtk::Switch::builder()
+            .active(rule.mail)
+            .valign(gtk::Align::Center)
+            .build();
+        mail_switch.connect_active_notify(clone!(@strong sender => move |switch| {
+            sender.input(PreferencesInputMessage::RuleMailToggled(number, switch.is_active()));
+        }));
+        let mail_row = adw::ActionRow::builder()
+            .title("Mail links")
+            .subtitle("Matches mailto links by the domain of the recipient instead of web links")
+            .build();
+        mail_row.add_suffix(&mail_switch);
+        mail_row.set_activatable_widget(Some(&mail_switch));
+        row.add_row(&mail_row);
+
+        let source_entry = gtk::Entry::builder()
+            .text(&rule.source)
+            .placeholder_text("slack")
+            .valign(gtk::Align::Center)
+            .build();
+        source_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleSourceEdited(number, entry.text().into()));
+        }));
+        let source_row = adw::ActionRow::builder()
+            .title("From application")
+            .subtitle("Empty means any, the names of the application are shown in the tooltip of the url")
+            .build();
+        source_row.add_suffix(&source_entry);
+        row.add_row(&source_row);
+
+        let targets = self.targets();
+        let target_dropdown = gtk::DropDown::from_strings(&targets.iter().map(|(_, name)| name.as_str()).collect::<Vec<&str>>());
+        target_dropdown.set_valign(gtk::Align::Center);
+        let target = targets.iter().position(|(id, _)| *id == rule.target);
+        target_dropdown.set_selected(target.map(|number| number as u32).unwrap_or(gtk::INVALID_LIST_POSITION));
+        target_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(PreferencesInputMessage::RuleTargetSelected(number, dropdown.selected()));
+        }));
+        let target_row = adw::ActionRow::builder().title("Open in").build();
+        target_row.add_suffix(&target_dropdown);
+        row.add_row(&target_row);
+
+        let days = gtk::Box::builder().css_classes(vec!["linked"]).valign(gtk::Align::Center).build();
+        for (day, label) in (1..).zip(DAY_LABELS) {
+            let toggle = gtk::ToggleButton::builder()
+                .label(label)
+                .active(rule.schedule.days.contains(&day))
+                .build();
+            toggle.connect_toggled(clone!(@strong sender => move |toggle| {
+                sender.input(PreferencesInputMessage::RuleDayToggled(number, day, toggle.is_active()));
+            }));
+            days.append(&toggle);
+        }
+        let days_row = adw::ActionRow::builder().title("Days").subtitle("None selected means every day").build();
+        days_row.add_suffix(&days);
+        row.add_row(&days_row);
+
+        let times_entry = gtk::Entry::builder()
+            .text(rule.schedule.format_times())
+            .placeholder_text("09:00-17:00")
+            .valign(gtk::Align::Center)
+            .build();
+        times_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleTimesEdited(number, entry.clone()));
+        }));
+        let times_row = adw::ActionRow::builder().title("Hours").subtitle("Comma separated ranges, empty means all day").build();
+        times_row.add_suffix(&times_entry);
+        row.add_row(&times_row);
+
+        let dates_entry = gtk::Entry::builder()
+            .text(rule.schedule.format_dates())
+            .placeholder_text("2026-12-24..2027-01-06")
+            .valign(gtk::Align::Center)
+            .build();
+        dates_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleDatesEdited(number, entry.clone()));
+        }));
+        let dates_row = adw::ActionRow::builder().title("Dates").subtitle("Comma separated dates and ranges, empty means any date").build();
+        dates_row.add_suffix(&dates_entry);
+        row.add_row(&dates_row);
+
+        let running_switch = gtk::Switch::builder()
+            .active(rule.running)
+            .valign(gtk::Align::Center)
+            .build();
+        running_switch.connect_active_notify(clone!(@strong sender => move |switch| {
+            sender.input(PreferencesInputMessage::RuleRunningToggled(number, switch.is_active()));
+        }));
+        let running_row = adw::ActionRow::builder().title("Only while the browser is running").build();
+        running_row.add_suffix(&running_switch);
+        running_row.set_activatable_widget(Some(&running_switch));
+        row.add_row(&running_row);
+
+        let private_switch = gtk::Switch::builder()
+            .active(rule.launch.private)
+            .valign(gtk::Align::Center)
+            .build();
+        private_switch.connect_active_notify(clone!(@strong sender => move |switch| {
+            sender.input(PreferencesInputMessage::RulePrivateToggled(number, switch.is_active()));
+        }));
+        let private_row = adw::ActionRow::builder().title("Open in a private window").build();
+        private_row.add_suffix(&private_switch);
+        private_row.set_activatable_widget(Some(&private_switch));
+        row.add_row(&private_row);
+
+        let app_mode_switch = gtk::Switch::builder()
+            .active(rule.launch.app_mode)
+            .valign(gtk::Align::Center)
+            .build();
+        app_mode_switch.connect_active_notify(clone!(@strong sender => move |switch| {
+            sender.input(PreferencesInputMessage::RuleAppModeToggled(number, switch.is_active()));
+        }));
+        let app_mode_row = adw::ActionRow::builder()
+            .title("Open as an app")
+            .subtitle("In a window without tabs and toolbars, only Chromium based browsers can do it")
+            .build();
+        app_mode_row.add_suffix(&app_mode_switch);
+        app_mode_row.set_activatable_widget(Some(&app_mode_switch));
+        row.add_row(&app_mode_row);
+
+        let throwaway_switch = gtk::Switch::builder()
+            .active(rule.launch.throwaway)
+            .valign(gtk::Align::Center)
+            .build();
+        throwaway_switch.connect_active_notify(clone!(@strong sender => move |switch| {
+            sender.input(PreferencesInputMessage::RuleThrowawayToggled(number, switch.is_active()));
+        }));
+        let throwaway_row = adw::ActionRow::builder()
+            .title("Open in a throwaway session")
+            .subtitle("With a temporary profile deleted after the browser exits")
+            .build();
+        throwaway_row.add_suffix(&throwaway_switch);
+        throwaway_row.set_activatable_widget(Some(&throwaway_switch));
+        row.add_row(&throwaway_row);
+
+        let (container_row, container_entry) = launch_entry_row("Firefox container", "Needs the Open external links in a container add-on", &rule.launch.container);
+        container_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleContainerEdited(number, entry.text().into()));
+        }));
+        row.add_row(&container_row);
+
+        let (webmail_row, webmail_entry) = launch_entry_row("Webmail", "Mail links are composed in it, empty means the default one", &rule.launch.webmail);
+        webmail_entry.set_placeholder_text(Some("Gmail"));
+        webmail_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleWebmailEdited(number, entry.text().into()));
+        }));
+        row.add_row(&webmail_row);
+
+        let (wrapper_row, wrapper_entry) = wrapper_row("Replaces the sandbox of the browser", &rule.launch.wrapper);
+        check_wrapper_entry(&wrapper_entry);
+        wrapper_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleWrapperEdited(number, entry.clone()));
+        }));
+        row.add_row(&wrapper_row);
+
+        let (args_row, args_entry) = launch_entry_row("Arguments", "Added to the arguments of the browser", &launch::format_args(&rule.launch.args));
+        args_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleArgsEdited(number, entry.clone()));
+        }));
+        row.add_row(&args_row);
+
+        let (env_row, env_entry) = launch_entry_row("Environment", "Added to the environment of the browser", &launch::format_env(&rule.launch.env));
+        env_entry.connect_changed(clone!(@strong sender => move |entry| {
+            sender.input(PreferencesInputMessage::RuleEnvEdited(number, entry.clone()));
+        }));
+        row.add_row(&env_row);
+
+        let actions_row = adw::ActionRow::new();
+        let move_up_button = gtk::Button::builder()
+            .label("Move up")
+            .valign(gtk::Align::Center)
+            .sensitive(number > 0)
+            .build();
+        move_up_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(PreferencesInputMessage::RuleMovedUp(number));
+        }));
+        actions_row.add_suffix(&move_up_button);
+        let remove_button = gtk::Button::builder()
+            .label("Remove rule")
+            .valign(gtk::Align::Center)
+            .css_classes(vec!["destructive-action"])
+            .build();
+        remove_button.connect_clicked(clone!(@strong sender => move |_| {
+            sender.input(PreferencesInputMessage::RuleRemoved(number));
+        }));
+        actions_row.add_suffix(&remove_button);
+        row.add_row(&actions_row);
+
+        row
+    }
+
+    fn reload_rule_rows(&mut self, sender: &ComponentSender<Self>) {
+        for row in self.rule_rows.drain(..) {
+            self.rules_group.remove(&row);
+        }
+        self.rule_rows = self.rules.iter().enumerate()
+            .map(|(number, rule)| self.rule_row(number, rule, sender))
+            .collect();
+        for number in 0..self.rule_rows.len() {
+            self.rules_group.add(&self.rule_rows[number]);
+            self.update_rule_row(number);
+        }
+    }
+
+    fn save_rule(&self, number: usize) {
+        rules::save_rules(&self.settings, &self.rules);
+        self.update_rule_row(number);
+    }
+
+    fn reload_hotkey_entries(&self) {
+        for (id, entry) in self.hotkey_entries.iter() {
+            let hotkey = self.hotkeys.get(id).copied().flatten();
+            entry.set_text(&hotkey.map(String::from).unwrap_or_default());
+            entry.remove_css_class("error");
+            entry.set_has_tooltip(false);
+        }
+    }
+}
+
+
+/// Makes a row with an entry for arguments or environment variables of a browser.
+fn launch_entry_row(title: &str, subtitle: &str, text: &str) -> (adw::ActionRow, gtk::Entry) {
+    let entry = gtk::Entry::builder()
+        .text(text)
+        .width_chars(30)
+        .valign(gtk::Align::Center)
+        .build();
+    let row = adw::ActionRow::builder().title(title).subtitle(subtitle).build();
+    row.add_suffix(&entry);
+    (row, entry)
+}
+
+
+/// Makes a row with an entry for a sandbox wrapper and a menu of presets.
+fn wrapper_row(subtitle: &str, wrapper: &[String]) -> (adw::ActionRow, gtk::Entry) {
+    let (row, entry) = launch_entry_row("Sandbox", subtitle, &launch::format_args(wrapper));
+    entry.set_placeholder_text(Some("firejail"));
+    let popover = gtk::Popover::new();
+    let presets = gtk::Box::new(gtk::Orientation::Vertical, 0);
+    for (name, command) in launch::WRAPPER_PRESETS {
+        let button = gtk::Button::builder()
+            .label(name)
+            .css_classes(vec!["flat"])
+            .build();
+        button.connect_clicked(clone!(@weak entry, @weak popover => move |_| {
+            popover.popdown();
+            entry.set_text(command);
+        }));
+        presets.append(&button);
+    }
+    popover.set_child(Some(&presets));
+    row.add_suffix(&gtk::MenuButton::builder()
+        .icon_name("view-more-symbolic")
+        .tooltip_text("Presets")
+        .valign(gtk::Align::Center)
+        .popover(&popover)
+        .build());
+    (row, entry)
+}
+
+
+/// Shows if the program of the wrapper in `entry` is installed, returns the wrapper if it is valid.
+fn check_wrapper_entry(entry: &gtk::Entry) -> Option<Vec<String>> {
+    let wrapper = launch::parse_args(&entry.text());
+    let reason = match &wrapper {
+        Some(wrapper) => launch::check_wrapper(wrapper).err(),
+        None => Some(String::from("Can not be parsed")),
+    };
+    entry.set_class_active("error", reason.is_some());
+    entry.set_tooltip_text(reason.as_deref());
+    wrapper
+}
+
+
+#[relm4::component(pub)]
+impl SimpleComponent for PreferencesModel {
+    type Input = PreferencesInputMessage;
+    type Output = PreferencesOutputMessage;
+    type Init = PreferencesInit;
+
+    view! {
+        adw::PreferencesWindow {
+            set_title: Some("Preferences"),
+            set_modal: true,
+            set_hide_on_close: true,
+            set_search_enabled: false,
+
+            add = &adw::PreferencesPage {
+                set_title: "General",
+                set_icon_name: Some("preferences-system-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Browsers",
+
+                    adw::ActionRow {
+                        set_title: "Order of browsers",
+                        set_subtitle: "Hotkeys stay the same in every order",
+                        #[local_ref]
+                        add_suffix = &browser_order_dropdown -> gtk::DropDown {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    adw::ActionRow {
+                        set_title: "Prefer running browsers",
+                        set_subtitle: "Select a running browser when there is no suggestion for the domain",
+                        set_activatable_widget: Some(&prefer_running_switch),
+                        #[local_ref]
+                        add_suffix = &prefer_running_switch -> gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Countdown",
+                    set_description: Some("Open the link automatically unless a key is pressed or the window is clicked"),
+
+                    adw::ActionRow {
+                        set_title: "Open automatically after seconds",
+                        set_subtitle: "Zero never opens automatically",
+                        #[local_ref]
+                        add_suffix = &countdown_spin -> gtk::SpinButton {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    adw::ActionRow {
+                        set_title: "Open in",
+                        #[local_ref]
+                        add_suffix = &countdown_fallback_dropdown -> gtk::DropDown {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    adw::ActionRow {
+                        set_title: "Seconds for applications",
+                        set_subtitle: "Overrides for links from some applications, e.g. slack=0, thunderbird=5",
+                        #[local_ref]
+                        add_suffix = &countdown_overrides_entry -> gtk::Entry {
+                            set_valign: gtk::Align::Center,
+                            connect_changed[sender] => move |entry| {
+                                sender.input(PreferencesInputMessage::CountdownOverridesEdited(entry.clone()));
+                            },
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Native apps",
+                    set_description: Some("Links of Zoom, Microsoft Teams, Spotify, Slack and Figma can be opened in their installed apps"),
+
+                    adw::ActionRow {
+                        set_title: "Open in native apps without asking",
+                        set_activatable_widget: Some(&handoff_switch),
+                        #[local_ref]
+                        add_suffix = &handoff_switch -> gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Media and documents",
+                    set_description: Some("Links to videos, audio and documents are offered to other applications too"),
+
+                    adw::ActionRow {
+                        set_title: "Check the type of links",
+                        set_subtitle: "Ask the server when the path of a link does not tell",
+                        set_activatable_widget: Some(&sniff_switch),
+                        #[local_ref]
+                        add_suffix = &sniff_switch -> gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    adw::ActionRow {
+                        set_title: "Open automatically",
+                        set_subtitle: "Applications for types of links, e.g. video/*=mpv.desktop",
+                        #[local_ref]
+                        add_suffix = &handlers_entry -> gtk::Entry {
+                            set_valign: gtk::Align::Center,
+                            connect_changed[sender] => move |entry| {
+                                sender.input(PreferencesInputMessage::HandlersEdited(entry.clone()));
+                            },
+                        },
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Hotkeys",
+                set_icon_name: Some("input-keyboard-symbolic"),
+
+                #[local_ref]
+                add = &hotkeys_group -> adw::PreferencesGroup {
+                    set_title: "Browsers",
+                    set_description: Some("Letters and digits are allowed, Q, S, H, D, M and E are used by BrowDi itself. Leave empty to remove a hotkey."),
+                },
+
+                #[local_ref]
+                add = &modifiers_group -> adw::PreferencesGroup {
+                    set_title: "Modifiers",
+                    set_description: Some("Action taken when a browser hotkey is pressed or a browser button is clicked while holding a modifier, Ctrl and Shift together always mark the browser"),
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Launch",
+                set_icon_name: Some("system-run-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Apps",
+                    set_description: Some("Links opened as an app are shown in a window without tabs and toolbars, only Chromium based browsers can do it. Firefox has just a fullscreen kiosk mode and Epiphany opens only web apps installed in it"),
+
+                    adw::ActionRow {
+                        set_title: "Separate profile for every app",
+                        set_subtitle: "Keeps logins of an app apart from the rest of the browser",
+                        set_activatable_widget: Some(&app_profiles_switch),
+                        #[local_ref]
+                        add_suffix = &app_profiles_switch -> gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Mail",
+                    set_description: Some("Mail links are offered to mail clients, browsers open them in a webmail. One webmail per line, a name and an url of its compose page where {to}, {cc}, {bcc}, {subject} and {body} are replaced with the fields of the mail."),
+
+                    adw::ActionRow {
+                        set_title: "Default webmail",
+                        set_subtitle: "Name of the webmail used unless a rule or the picker chooses another one",
+                        #[local_ref]
+                        add_suffix = &default_webmail_entry -> gtk::Entry {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    gtk::Frame {
+                        set_margin_top: 12,
+
+                        #[local_ref]
+                        webmail_templates_view -> gtk::TextView {
+                            set_monospace: true,
+                            set_wrap_mode: gtk::WrapMode::None,
+                            set_top_margin: 6,
+                            set_bottom_margin: 6,
+                            set_left_margin: 6,
+                            set_right_margin: 6,
+                        },
+                    },
+                },
+
+                #[local_ref]
+                add = &launch_group -> adw::PreferencesGroup {
+                    set_title: "Browsers",
+                    set_description: Some("Arguments are written as in a shell, %u is replaced with the url, e.g. --new-window or --app=%u. Variables are written as NAME=value, e.g. MOZ_ENABLE_WAYLAND=1. A sandbox is a command put before the command of the browser, a link is not opened if the sandbox is not installed."),
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Rules",
+                set_icon_name: Some("preferences-other-symbolic"),
+
+                #[local_ref]
+                add = &rules_group -> adw::PreferencesGroup {
+                    set_title: "Rules",
+                    set_description: Some("Links to a domain are opened without asking when the current day and time match a rule, the first matching rule wins"),
+                },
+
+                #[local_ref]
+                add = &groups_group -> adw::PreferencesGroup {
+                    set_title: "Groups",
+                    set_description: Some("Rules can open links in all browsers of a group at once. Mark browsers with Ctrl+Shift and a hotkey or a click and save them as a group in the picker."),
+                },
+
+                add = &adw::PreferencesGroup {
+                    gtk::Button {
+                        set_label: "Add rule",
+                        set_halign: gtk::Align::Center,
+                        add_css_class: "pill",
+                        connect_clicked => PreferencesInputMessage::RuleAdded,
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "Search",
+                set_icon_name: Some("system-search-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Search engines",
+                    set_description: Some("Text that is not a link is searched. One engine per line, a keyword and an url where %s is the query, e.g. typing gh relm4 searches GitHub."),
+
+                    adw::ActionRow {
+                        set_title: "Default search engine",
+                        set_subtitle: "Keyword of the engine used without a keyword",
+                        #[local_ref]
+                        add_suffix = &default_engine_entry -> gtk::Entry {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    gtk::Frame {
+                        set_margin_top: 12,
+
+                        #[local_ref]
+                        search_engines_view -> gtk::TextView {
+                            set_monospace: true,
+                            set_wrap_mode: gtk::WrapMode::None,
+                            set_top_margin: 6,
+                            set_bottom_margin: 6,
+                            set_left_margin: 6,
+                            set_right_margin: 6,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Url templates",
+                    set_description: Some("Text matching a template becomes a link. One template per line, a regular expression matching the whole text and an url where \\1 is the first group, e.g. ([A-Z]+-[0-9]+) https://jira.example.com/browse/\\1"),
+
+                    gtk::Frame {
+                        #[local_ref]
+                        url_templates_view -> gtk::TextView {
+                            set_monospace: true,
+                            set_wrap_mode: gtk::WrapMode::None,
+                            set_top_margin: 6,
+                            set_bottom_margin: 6,
+                            set_left_margin: 6,
+                            set_right_margin: 6,
+                        },
+                    },
+                },
+            },
+
+            add = &adw::PreferencesPage {
+                set_title: "History",
+                set_icon_name: Some("document-open-recent-symbolic"),
+
+                add = &adw::PreferencesGroup {
+                    set_title: "History",
+                    set_description: Some("Every opened link is recorded locally, zero means no limit"),
+
+                    adw::ActionRow {
+                        set_title: "Keep history",
+                        #[local_ref]
+                        add_suffix = &history_enabled_switch -> gtk::Switch {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    adw::ActionRow {
+                        set_title: "Maximum number of entries",
+                        #[local_ref]
+                        add_suffix = &history_max_entries_spin -> gtk::SpinButton {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                    adw::ActionRow {
+                        set_title: "Maximum age in days",
+                        #[local_ref]
+                        add_suffix = &history_max_days_spin -> gtk::SpinButton {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                },
+
+                add = &adw::PreferencesGroup {
+                    set_title: "Suggestions",
+                    set_description: Some("The browser chosen most often for a domain is selected when the domain is opened again"),
+
+                    adw::ActionRow {
+                        set_title: "Offer to remember a browser after the same choices in a row",
+                        set_subtitle: "Zero never offers",
+                        #[local_ref]
+                        add_suffix = &rule_prompt_spin -> gtk::SpinButton {
+                            set_valign: gtk::Align::Center,
+                        },
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(
+        init: Self::Init,
+        window: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let hotkeys_group = adw::PreferencesGroup::new();
+        let mut hotkey_entries = HashMap::new();
+        for browser in init.browsers.iter() {
+            let Some(id) = browser.id().map(String::from) else { continue };
+            let row = adw::ActionRow::builder().title(browser.name()).build();
+            if let Some(icon) = browser.icon() {
+                row.add_prefix(&gtk::Image::from_gicon(&icon));
+            }
+            let entry = gtk::Entry::builder()
+                .max_length(1)
+                .width_chars(2)
+                .xalign(0.5)
+                .valign(gtk::Align::Center)
+                .build();
+            entry.connect_changed(clone!(@strong sender, @strong id => move |entry| {
+                sender.input(PreferencesInputMessage::HotkeyEdited(id.clone(), entry.text().into()));
+            }));
+            row.add_suffix(&entry);
+            hotkeys_group.add(&row);
+            hotkey_entries.insert(id, entry);
+        }
+
+        let app_profiles_switch = gtk::Switch::new();
+        init.settings.bind("app-mode-profiles", &app_profiles_switch, "active").build();
+
+        let launch_group = adw::PreferencesGroup::new();
+        for (number, browser) in init.browsers.iter().enumerate() {
+            let options = LaunchOptions::load(&init.settings, browser);
+            let row = adw::ExpanderRow::builder().title(browser.name()).build();
+            if let Some(icon) = browser.icon() {
+                row.add_prefix(&gtk::Image::from_gicon(&icon));
+            }
+            let (args_row, args_entry) = launch_entry_row("Arguments", "Added to the command line of the desktop file", &launch::format_args(&options.args));
+            args_entry.connect_changed(clone!(@strong sender => move |entry| {
+                sender.input(PreferencesInputMessage::BrowserArgsEdited(number, entry.clone()));
+            }));
+            row.add_row(&args_row);
+            let (env_row, env_entry) = launch_entry_row("Environment", "Variables set for the browser", &launch::format_env(&options.env));
+            env_entry.connect_changed(clone!(@strong sender => move |entry| {
+                sender.input(PreferencesInputMessage::BrowserEnvEdited(number, entry.clone()));
+            }));
+            row.add_row(&env_row);
+            let (wrapper_row, wrapper_entry) = wrapper_row("Command the browser is started with", &options.wrapper);
+            check_wrapper_entry(&wrapper_entry);
+            wrapper_entry.connect_changed(clone!(@strong sender => move |entry| {
+                sender.input(PreferencesInputMessage::BrowserWrapperEdited(number, entry.clone()));
+            }));
+            row.add_row(&wrapper_row);
+            launch_group.add(&row);
+        }
+
+        let default_webmail_entry = gtk::Entry::builder().width_chars(12).build();
+        init.settings.bind("default-webmail", &default_webmail_entry, "text").build();
+        let webmail_templates_view = gtk::TextView::new();
+        webmail_templates_view.buffer().set_text(&mail::format_templates(&init.settings.get("webmail-templates")));
+        webmail_templates_view.buffer().connect_changed(clone!(@strong sender, @weak webmail_templates_view => move |_| {
+            sender.input(PreferencesInputMessage::WebmailTemplatesEdited(webmail_templates_view));
+        }));
+
+        let modifiers_group = adw::PreferencesGroup::new();
+        let modifier_actions = hotkeys::load_modifier_actions(&init.settings);
+        let action_descriptions: Vec<&str> = LaunchAction::ALL.iter().map(LaunchAction::description).collect();
+        for modifier in LaunchModifier::ALL {
+            let row = adw::ActionRow::builder().title(modifier.label()).build();
+            let dropdown = gtk::DropDown::from_strings(&action_descriptions);
+            dropdown.set_valign(gtk::Align::Center);
+            let action = modifier_actions.get(&modifier).copied().unwrap_or_default();
+            if let Some(position) = LaunchAction::ALL.iter().position(|a| *a == action) {
+                dropdown.set_selected(position as u32);
+            }
+            dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+                sender.input(PreferencesInputMessage::ModifierActionSelected(modifier, dropdown.selected()));
+            }));
+            row.add_suffix(&dropdown);
+            modifiers_group.add(&row);
+        }
+
+        let order_descriptions: Vec<&str> = BrowserOrder::ALL.iter().map(BrowserOrder::description).collect();
+        let browser_order_dropdown = gtk::DropDown::from_strings(&order_descriptions);
+        let browser_order = BrowserOrder::load(&init.settings);
+        if let Some(position) = BrowserOrder::ALL.iter().position(|order| *order == browser_order) {
+            browser_order_dropdown.set_selected(position as u32);
+        }
+        browser_order_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(PreferencesInputMessage::BrowserOrderSelected(dropdown.selected()));
+        }));
+
+        let prefer_running_switch = gtk::Switch::new();
+        init.settings.bind("prefer-running-browsers", &prefer_running_switch, "active").build();
+
+        let countdown_spin = gtk::SpinButton::with_range(0.0, sources::MAX_COUNTDOWN_SECONDS as f64, 1.0);
+        init.settings.bind("countdown-seconds", &countdown_spin, "value").build();
+        let fallback_names: Vec<String> = std::iter::once(String::from("Suggested browser"))
+            .chain(init.browsers.iter().map(|browser| browser.name().to_string()))
+            .collect();
+        let countdown_fallback_dropdown = gtk::DropDown::from_strings(&fallback_names.iter().map(String::as_str).collect::<Vec<&str>>());
+        let fallback = init.settings.string("countdown-fallback");
+        if let Some(position) = init.browsers.iter().position(|browser| browser.id().is_some_and(|id| id == fallback)) {
+            countdown_fallback_dropdown.set_selected(position as u32 + 1);
+        }
+        countdown_fallback_dropdown.connect_selected_notify(clone!(@strong sender => move |dropdown| {
+            sender.input(PreferencesInputMessage::CountdownFallbackSelected(dropdown.selected()));
+        }));
+
+        let countdown_overrides_entry = gtk::Entry::new();
+        countdown_overrides_entry.set_text(&sources::format_countdown_overrides(&init.settings.get("countdown-seconds-for-sources")));
+
+        let handoff_switch = gtk::Switch::new();
+        init.settings.bind("handoff-automatically", &handoff_switch, "active").build();
+        let sniff_switch = gtk::Switch::new();
+        init.settings.bind("sniff-content-types", &sniff_switch, "active").build();
+        let handlers_entry = gtk::Entry::new();
+        handlers_entry.set_text(&media::format_handlers(&init.settings.get("handlers-for-types")));
+
+        let default_engine_entry = gtk::Entry::builder().width_chars(8).build();
+        init.settings.bind("default-search-engine", &default_engine_entry, "text").build();
+        let search_engines_view = gtk::TextView::new();
+        search_engines_view.buffer().set_text(&queries::format_engines(&init.settings.get("search-engines")));
+        search_engines_view.buffer().connect_changed(clone!(@strong sender, @weak search_engines_view => move |_| {
+            sender.input(PreferencesInputMessage::SearchEnginesEdited(search_engines_view));
+        }));
+        let url_templates_view = gtk::TextView::new();
+        let templates: Vec<(String, String)> = init.settings.get("url-templates");
+        url_templates_view.buffer().set_text(&queries::format_templates(&templates));
+        url_templates_view.buffer().connect_changed(clone!(@strong sender, @weak url_templates_view => move |_| {
+            sender.input(PreferencesInputMessage::UrlTemplatesEdited(url_templates_view));
+        }));
+
+        let history_enabled_switch = gtk::Switch::new();
+        init.settings.bind("history-enabled", &history_enabled_switch, "active").build();
+        let history_max_entries_spin = gtk::SpinButton::with_range(0.0, 100000.0, 100.0);
+        init.settings.bind("history-max-entries", &history_max_entries_spin, "value").build();
+        let history_max_days_spin = gtk::SpinButton::with_range(0.0, 3650.0, 1.0);
+        init.settings.bind("history-max-days", &history_max_days_spin, "value").build();
+        let rule_prompt_spin = gtk::SpinButton::with_range(0.0, 20.0, 1.0);
+        init.settings.bind("rule-prompt-after-choices", &rule_prompt_spin, "value").build();
+
+        let rules_group = adw::PreferencesGroup::new();
+        let groups_group = adw::PreferencesGroup::new();
+
+        let mut model = PreferencesModel {
+            window: window.clone(),
+            hotkeys: hotkeys::load(&init.settings, &init.browsers),
+            groups: groups::load(&init.settings),
+            browsers: init.browsers,
+            settings: init.settings,
+            hotkey_entries,
+            rules: Vec::new(),
+            rules_group: rules_group.clone(),
+            rule_rows: Vec::new(),
+            groups_group: groups_group.clone(),
+            group_rows: Vec::new(),
+        };
+        model.reload_hotkey_entries();
+        model.reload_group_rows(&sender);
+        model.rules = rules::load_rules(&model.settings);
+        model.reload_rule_rows(&sender);
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
+        match message {
+            PreferencesInputMessage::Show => {
+                self.hotkeys = hotkeys::load(&self.settings, &self.browsers);
+                self.reload_hotkey_entries();
+                self.groups = groups::load(&self.settings);
+                self.reload_group_rows(&sender);
+                self.rules = rules::load_rules(&self.settings);
+                self.reload_rule_rows(&sender);
+                self.window.present();
+            }
+            PreferencesInputMessage::HotkeyEdited(id, text) => {
+                let Some(entry) = self.hotkey_entries.get(&id) else { return };
+                let hotkey = match text.chars().next() {
+                    None => Ok(None),
+                    Some(key) => hotkeys::check(key, &id, &self.hotkeys).map(Some),
+                };
+                match hotkey {
+                    Ok(hotkey) => {
+                        entry.remove_css_class("error");
+                        entry.set_has_tooltip(false);
+                        let text = hotkey.map(String::from).unwrap_or_default();
+                        if entry.text() != text {
+                            entry.set_text(&text);
+                        }
+                        if self.hotkeys.get(&id) != Some(&hotkey) {
+                            self.hotkeys.insert(id.clone(), hotkey);
+                            hotkeys::save(&self.settings, &id, hotkey);
+                            sender.output(PreferencesOutputMessage::HotkeysChanged).unwrap();
+                        }
+                    }
+                    Err(conflict) => {
+                        let reason = match conflict {
+                            HotkeyConflict::Invalid => String::from("Only letters and digits can be used"),
+                            HotkeyConflict::Reserved => format!("{} is used by BrowDi itself", text.to_uppercase()),
+                            HotkeyConflict::Browser(other) => format!("{} is already used by {}", text.to_uppercase(), self.browser_name(&other)),
+                        };
+                        entry.add_css_class("error");
+                        entry.set_tooltip(&reason);
+                    }
+                }
+            }
+            PreferencesInputMessage::BrowserOrderSelected(position) => {
+                if let Some(order) = BrowserOrder::ALL.get(position as usize) {
+                    order.save(&self.settings);
+                    sender.output(PreferencesOutputMessage::BrowserOrderChanged).unwrap();
+                }
+            }
+            PreferencesInputMessage::CountdownFallbackSelected(position) => {
+                let fallback = (position as usize).checked_sub(1)
+                    .and_then(|number| self.browsers.get(number))
+                    .and_then(|browser| browser.id())
+                    .map(String::from)
+                    .unwrap_or_default();
+                let _ = self.settings.set_string("countdown-fallback", &fallback);
+            }
+            PreferencesInputMessage::RuleAdded => {
+                let target = self.browsers.first().and_then(|browser| browser.id()).map(String::from).unwrap_or_default();
+                self.rules.push(Rule { target, ..Rule::default() });
+                rules::save_rules(&self.settings, &self.rules);
+                self.reload_rule_rows(&sender);
+                if let Some(row) = self.rule_rows.last() {
+                    row.set_expanded(true);
+                }
+            }
+            PreferencesInputMessage::RuleRemoved(number) => {
+                if number < self.rules.len() {
+                    self.rules.remove(number);
+                    rules::save_rules(&self.settings, &self.rules);
+                    self.reload_rule_rows(&sender);
+                }
+            }
+            PreferencesInputMessage::RuleMovedUp(number) => {
+                if number > 0 && number < self.rules.len() {
+                    self.rules.swap(number - 1, number);
+                    rules::save_rules(&self.settings, &self.rules);
+                    self.reload_rule_rows(&sender);
+                    self.rule_rows[number - 1].set_expanded(true);
+                }
+            }
+            PreferencesInputMessage::RuleDomainEdited(number, domain) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.domain = domain.trim().to_string();
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleSourceEdited(number, source) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.source = source.trim().to_string();
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleTargetSelected(number, position) => {
+                let target = self.targets().into_iter().nth(position as usize).map(|(id, _)| id);
+                let (Some(rule), Some(target)) = (self.rules.get_mut(number), target) else { return };
+                rule.target = target;
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::GroupRemoved(number) => {
+                let Some((name, _)) = self.groups.get(number) else { return };
+                groups::remove(&self.settings, name);
+                self.groups = groups::load(&self.settings);
+                self.reload_group_rows(&sender);
+                self.reload_rule_rows(&sender);
+                sender.output(PreferencesOutputMessage::GroupsChanged).unwrap();
+            }
+            PreferencesInputMessage::RuleDayToggled(number, day, active) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.schedule.days.retain(|d| *d != day);
+                if active {
+                    rule.schedule.days.push(day);
+                    rule.schedule.days.sort();
+                }
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleRunningToggled(number, running) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.running = running;
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleMailToggled(number, mail) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.mail = mail;
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RulePrivateToggled(number, private) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.launch.private = private;
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleAppModeToggled(number, app_mode) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.launch.app_mode = app_mode;
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleThrowawayToggled(number, throwaway) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.launch.throwaway = throwaway;
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleContainerEdited(number, container) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.launch.container = container.trim().to_string();
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleWebmailEdited(number, webmail) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                rule.launch.webmail = webmail.trim().to_string();
+                self.save_rule(number);
+            }
+            PreferencesInputMessage::RuleWrapperEdited(number, entry) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                if let Some(wrapper) = check_wrapper_entry(&entry) {
+                    rule.launch.wrapper = wrapper;
+                    self.save_rule(number);
+                }
+            }
+            PreferencesInputMessage::BrowserWrapperEdited(number, entry) => {
+                let Some(browser) = self.browsers.get(number) else { return };
+                if let Some(wrapper) = check_wrapper_entry(&entry) {
+                    let options = LaunchOptions { wrapper, ..LaunchOptions::load(&self.settings, browser) };
+                    options.save(&self.settings, browser);
+                }
+            }
+            PreferencesInputMessage::RuleArgsEdited(number, entry) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                match launch::parse_args(&entry.text()) {
+                    Some(args) => {
+                        entry.remove_css_class("error");
+                        rule.launch.args = args;
+                        self.save_rule(number);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::RuleEnvEdited(number, entry) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                match launch::parse_env(&entry.text()) {
+                    Some(env) => {
+                        entry.remove_css_class("error");
+                        rule.launch.env = env;
+                        self.save_rule(number);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::BrowserArgsEdited(number, entry) => {
+                let Some(browser) = self.browsers.get(number) else { return };
+                match launch::parse_args(&entry.text()) {
+                    Some(args) => {
+                        entry.remove_css_class("error");
+                        let options = LaunchOptions { args, ..LaunchOptions::load(&self.settings, browser) };
+                        options.save(&self.settings, browser);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::BrowserEnvEdited(number, entry) => {
+                let Some(browser) = self.browsers.get(number) else { return };
+                match launch::parse_env(&entry.text()) {
+                    Some(env) => {
+                        entry.remove_css_class("error");
+                        let options = LaunchOptions { env, ..LaunchOptions::load(&self.settings, browser) };
+                        options.save(&self.settings, browser);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::RuleTimesEdited(number, entry) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                match rules::parse_times(&entry.text()) {
+                    Some(times) => {
+                        entry.remove_css_class("error");
+                        rule.schedule.times = times;
+                        self.save_rule(number);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::RuleDatesEdited(number, entry) => {
+                let Some(rule) = self.rules.get_mut(number) else { return };
+                match rules::parse_dates(&entry.text()) {
+                    Some(dates) => {
+                        entry.remove_css_class("error");
+                        rule.schedule.dates = dates;
+                        self.save_rule(number);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::CountdownOverridesEdited(entry) => {
+                match sources::parse_countdown_overrides(&entry.text()) {
+                    Some(overrides) => {
+                        entry.remove_css_class("error");
+                        let _ = self.settings.set("countdown-seconds-for-sources", overrides);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::HandlersEdited(entry) => {
+                match media::parse_handlers(&entry.text()) {
+                    Some(handlers) => {
+                        entry.remove_css_class("error");
+                        let _ = self.settings.set("handlers-for-types", handlers);
+                    }
+                    None => entry.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::SearchEnginesEdited(view) => {
+                let buffer = view.buffer();
+                match queries::parse_engines(&buffer.text(&buffer.start_iter(), &buffer.end_iter(), false)) {
+                    Some(engines) => {
+                        view.remove_css_class("error");
+                        let _ = self.settings.set("search-engines", engines);
+                    }
+                    None => view.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::UrlTemplatesEdited(view) => {
+                let buffer = view.buffer();
+                match queries::parse_templates(&buffer.text(&buffer.start_iter(), &buffer.end_iter(), false)) {
+                    Some(templates) => {
+                        view.remove_css_class("error");
+                        let _ = self.settings.set("url-templates", templates);
+                    }
+                    None => view.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::WebmailTemplatesEdited(view) => {
+                let buffer = view.buffer();
+                match mail::parse_templates(&buffer.text(&buffer.start_iter(), &buffer.end_iter(), false)) {
+                    Some(templates) => {
+                        view.remove_css_class("error");
+                        let _ = self.settings.set("webmail-templates", templates);
+                    }
+                    None => view.add_css_class("error"),
+                }
+            }
+            PreferencesInputMessage::ModifierActionSelected(modifier, position) => {
+                if let Some(action) = LaunchAction::ALL.get(position as usize) {
+                    hotkeys::save_modifier_action(&self.settings, modifier, *action);
+                    sender.output(PreferencesOutputMessage::ModifierActionsChanged).unwrap();
+                }
+            }
+        }
+    }
+}
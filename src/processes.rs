@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+use gio::prelude::*;
+use gio::{glib, AppInfo};
+
+
+/// Directories shared by unrelated executables, a browser is not matched by them alone.
+const SHARED_DIRS: [&str; 6] = ["/usr/bin", "/bin", "/usr/local/bin", "/usr/sbin", "/sbin", "/usr/lib"];
+
+
+/// Executables of processes running as the current user, read from `/proc`.
+#[derive(Debug, Default)]
+pub struct RunningProcesses {
+    paths: HashSet<PathBuf>,
+    names: HashSet<String>,
+    /// Lines of `/proc/<pid>/cgroup`, they contain ids of flatpak apps and of apps launched by a desktop.
+    cgroups: HashSet<String>,
+}
+
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_name().map(|name| name.to_string_lossy().into_owned())
+}
+
+
+impl RunningProcesses {
+    pub fn scan() -> Self {
+        let mut running = RunningProcesses::default();
+        let Ok(uid) = fs::metadata("/proc/self").map(|metadata| metadata.uid()) else { return running };
+        let Ok(entries) = fs::read_dir("/proc") else { return running };
+        for entry in entries.flatten() {
+            let dir = entry.path();
+            if !file_name(&dir).is_some_and(|name| name.chars().all(|c| c.is_ascii_digit())) {
+                continue;
+            }
+            if !entry.metadata().is_ok_and(|metadata| metadata.uid() == uid) {
+                continue;
+            }
+            if let Ok(exe) = fs::read_link(dir.join("exe")) {
+                let exe = PathBuf::from(exe.to_string_lossy().trim_end_matches(" (deleted)"));
+                running.names.extend(file_name(&exe));
+                running.paths.insert(exe);
+            }
+            if let Ok(cmdline) = fs::read(dir.join("cmdline")) {
+                let program = cmdline.split(|byte| *byte == 0).next().unwrap_or_default();
+                running.names.extend(file_name(Path::new(&*String::from_utf8_lossy(program))));
+            }
+            if let Ok(cgroup) = fs::read_to_string(dir.join("cgroup")) {
+                running.cgroups.extend(cgroup.lines().map(String::from));
+            }
+        }
+        running
+    }
+
+    /// Checks if `app` has a running process, matching its executable by path, by name or by
+    /// the directory it is installed to, and flatpak apps by their sandbox.
+    pub fn is_running(&self, app: &AppInfo) -> bool {
+        let executable = app.executable();
+        if file_name(&executable).is_some_and(|name| name == "flatpak") {
+            let Some(id) = app.id() else { return false };
+            let scope = format!("app-flatpak-{}-", id.trim_end_matches(".desktop"));
+            return self.cgroups.iter().any(|cgroup| cgroup.contains(&scope));
+        }
+        let Some(path) = glib::find_program_in_path(&executable) else { return false };
+        let resolved = fs::canonicalize(&path).unwrap_or(path.clone());
+        if self.paths.contains(&path) || self.paths.contains(&resolved) {
+            return true;
+        }
+        if [&executable, &path, &resolved].iter().filter_map(|path| file_name(path)).any(|name| self.names.contains(&name)) {
+            return true;
+        }
+        // Browsers like Chrome start through a script that runs another binary from the same directory.
+        resolved.parent()
+            .filter(|dir| !SHARED_DIRS.iter().any(|shared| *dir == Path::new(shared)))
+            .is_some_and(|dir| self.paths.iter().any(|exe| exe.starts_with(dir)))
+    }
+}
@@ -0,0 +1,35 @@
+use qrcodegen::{QrCode, QrCodeEcc};
+use relm4::gtk;
+use relm4::gtk::cairo;
+
+
+/// Modules of white space around the code, scanners need at least four.
+const QUIET_ZONE: i32 = 4;
+
+
+/// Draw function of a drawing area showing `text` as a QR code, the code is made locally
+/// so the url is not sent anywhere.
+pub fn draw_func(text: Option<String>) -> impl Fn(&gtk::DrawingArea, &cairo::Context, i32, i32) + 'static {
+    let code = text.and_then(|text| QrCode::encode_text(&text, QrCodeEcc::Medium).ok());
+    move |_, context, width, height| {
+        let Some(code) = &code else { return };
+        let modules = code.size() + 2 * QUIET_ZONE;
+        let module = (width.min(height) / modules).max(1);
+        let left = (width - module * modules) / 2;
+        let top = (height - module * modules) / 2;
+        context.set_source_rgb(1.0, 1.0, 1.0);
+        context.rectangle(left.into(), top.into(), (module * modules).into(), (module * modules).into());
+        let _ = context.fill();
+        context.set_source_rgb(0.0, 0.0, 0.0);
+        for y in 0..code.size() {
+            for x in 0..code.size() {
+                if code.get_module(x, y) {
+                    let x = left + (x + QUIET_ZONE) * module;
+                    let y = top + (y + QUIET_ZONE) * module;
+                    context.rectangle(x.into(), y.into(), module.into(), module.into());
+                }
+            }
+        }
+        let _ = context.fill();
+    }
+}
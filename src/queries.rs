@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use gio::prelude::*;
+use gio::{glib, File, Settings};
+
+use crate::urls;
+
+
+/// Placeholder of the query in urls of search engines.
+const QUERY_PLACEHOLDER: &str = "%s";
+
+
+/// Url of `query` in the search engine `template`.
+fn search_url(template: &str, query: &str) -> String {
+    template.replace(QUERY_PLACEHOLDER, &glib::Uri::escape_string(query, None, false))
+}
+
+
+/// Url made from `text` by the first template which pattern matches the whole text, e.g. a Jira
+/// key like `PROJ-123` or a GitHub issue like `org/repo#123`. `\1` in a template is replaced with
+/// the first group of the pattern and so on.
+fn template_url(templates: &[(String, String)], text: &str) -> Option<String> {
+    templates.iter().find_map(|(pattern, template)| {
+        let pattern = format!("^(?:{pattern})$");
+        // matching a text that does not match fails an assertion of glib-rs in debug builds
+        if !glib::Regex::match_simple(&pattern, text, glib::RegexCompileFlags::DEFAULT, glib::RegexMatchFlags::DEFAULT) {
+            return None;
+        }
+        let regex = glib::Regex::new(&pattern, glib::RegexCompileFlags::DEFAULT, glib::RegexMatchFlags::DEFAULT).ok()??;
+        let text = glib::GString::from(text);
+        let matched = regex.match_(text.as_gstr(), glib::RegexMatchFlags::DEFAULT).filter(|matched| matched.matches())?;
+        matched.expand_references(template).ok()?.map(String::from)
+    })
+}
+
+
+/// Templates and search engines text typed by the user is turned into a link with.
+pub struct Queries {
+    templates: Vec<(String, String)>,
+    engines: HashMap<String, String>,
+    default_engine: String,
+}
+
+
+impl Queries {
+    pub fn load(settings: &Settings) -> Self {
+        Self {
+            templates: settings.get("url-templates"),
+            engines: settings.get("search-engines"),
+            default_engine: settings.string("default-search-engine").into(),
+        }
+    }
+
+    /// Turns text that is not a link into one: templates are tried first, then search engines picked
+    /// by the first word like `gh relm4`, then the text as a link without a scheme like `example.com`.
+    /// Anything else is looked up in the default search engine.
+    pub fn to_url(&self, text: &str) -> Option<String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return None;
+        }
+        if let Some(url) = self.shortcut_url(text) {
+            return Some(url);
+        }
+        if urls::has_scheme(text) || !text.contains(char::is_whitespace) {
+            if let Some(url) = urls::link(text) {
+                return Some(url);
+            }
+        }
+        let template = self.engines.get(&self.default_engine)?;
+        Some(search_url(template, text))
+    }
+
+    /// Url made from `text` by a template or by a search engine picked by its first word.
+    fn shortcut_url(&self, text: &str) -> Option<String> {
+        template_url(&self.templates, text).or_else(|| {
+            let (keyword, query) = text.split_once(char::is_whitespace)?;
+            Some(search_url(self.engines.get(keyword)?, query.trim()))
+        })
+    }
+}
+
+
+/// See [`Queries::to_url`].
+pub fn to_url(settings: &Settings, text: &str) -> Option<String> {
+    Queries::load(settings).to_url(text)
+}
+
+
+/// Formats search engines one per line as a keyword and an url.
+pub fn format_engines(engines: &HashMap<String, String>) -> String {
+    let mut lines: Vec<String> = engines.iter().map(|(keyword, url)| format!("{keyword} {url}")).collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+
+/// Parses search engines written one per line as a keyword and an url with `%s`, returns `None`
+/// if any of them is invalid.
+pub fn parse_engines(text: &str) -> Option<HashMap<String, String>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (keyword, url) = line.trim().split_once(char::is_whitespace)?;
+            let url = url.trim();
+            url.contains(QUERY_PLACEHOLDER).then(|| (keyword.to_string(), url.to_string()))
+        })
+        .collect()
+}
+
+
+/// Formats url templates one per line as a pattern and an url.
+pub fn format_templates(templates: &[(String, String)]) -> String {
+    templates.iter().map(|(pattern, url)| format!("{pattern} {url}")).collect::<Vec<_>>().join("\n")
+}
+
+
+/// Parses url templates written one per line as a pattern and an url, the url is the last word
+/// of a line so patterns may contain spaces. Returns `None` if any pattern is invalid.
+pub fn parse_templates(text: &str) -> Option<Vec<(String, String)>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (pattern, url) = line.trim().rsplit_once(char::is_whitespace)?;
+            let pattern = pattern.trim();
+            glib::Regex::new(pattern, glib::RegexCompileFlags::DEFAULT, glib::RegexMatchFlags::DEFAULT).ok()??;
+            Some((pattern.to_string(), url.to_string()))
+        })
+        .collect()
+}
+
+
+/// Extensions of files that look like top level domains, `notes.txt` given on the command line is
+/// a missing file rather than a website.
+const FILE_EXTENSIONS: [&str; 16] = [
+    "txt", "md", "pdf", "html", "htm", "xhtml", "mhtml", "svg", "png", "jpg", "jpeg", "gif", "webp", "json", "xml", "csv",
+];
+
+
+/// Checks if `text` starts with a host name like `example.com` or `localhost:3000`.
+fn has_host(text: &str) -> bool {
+    let host = text.split(['/', '?', '#']).next().unwrap_or_default();
+    let (name, port) = host.split_once(':').unwrap_or((host, ""));
+    if !port.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    if name.eq_ignore_ascii_case("localhost") || name.parse::<std::net::Ipv4Addr>().is_ok() {
+        return true;
+    }
+    let labels: Vec<&str> = name.split('.').collect();
+    let Some(tld) = labels.last().filter(|_| labels.len() > 1) else { return false };
+    labels.iter().all(|label| !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-'))
+        && tld.len() > 1
+        && tld.chars().all(char::is_alphabetic)
+        && !FILE_EXTENSIONS.contains(&tld.to_lowercase().as_str())
+}
+
+
+/// File of a command line argument of BrowDi. An argument that is neither a link nor an existing
+/// path is turned into a link like text typed in the launcher if it is a search, matches a template
+/// or a search keyword or starts with a host name, anything else is a missing file.
+pub fn file_for_argument(settings: &Settings, arg: &OsStr) -> File {
+    let queries = Queries::load(settings);
+    let expanded = arg.to_str()
+        .map(str::trim)
+        .filter(|text| !Path::new(text).exists() && !urls::has_scheme(text) && !text.starts_with(['/', '.', '~']))
+        .filter(|text| text.contains(char::is_whitespace) || has_host(text) || queries.shortcut_url(text).is_some())
+        .and_then(|text| queries.to_url(text));
+    match expanded {
+        Some(url) => File::for_uri(&url),
+        None => File::for_commandline_arg(arg),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queries() -> Queries {
+        Queries {
+            templates: parse_templates("([A-Z]+-[0-9]+) https://jira.example.com/browse/\\1\n([A-Za-z0-9_.-]+)/([A-Za-z0-9_.-]+)#([0-9]+) https://github.com/\\1/\\2/issues/\\3").unwrap(),
+            engines: parse_engines("ddg https://duckduckgo.com/?q=%s\ngh https://github.com/search?q=%s").unwrap(),
+            default_engine: String::from("ddg"),
+        }
+    }
+
+    #[test]
+    fn turns_text_into_urls() {
+        let queries = queries();
+        assert_eq!(queries.to_url("PROJ-123").as_deref(), Some("https://jira.example.com/browse/PROJ-123"));
+        assert_eq!(queries.to_url("relm4/relm4#42").as_deref(), Some("https://github.com/relm4/relm4/issues/42"));
+        assert_eq!(queries.to_url("gh relm4 factory").as_deref(), Some("https://github.com/search?q=relm4%20factory"));
+        assert_eq!(queries.to_url(" example.com/docs ").as_deref(), Some("https://example.com/docs"));
+        assert_eq!(queries.to_url("localhost:3000").as_deref(), Some("http://localhost:3000"));
+        assert_eq!(queries.to_url("https://example.com/a b").as_deref(), Some("https://example.com/ab"));
+        assert_eq!(queries.to_url("rust & gtk").as_deref(), Some("https://duckduckgo.com/?q=rust%20%26%20gtk"));
+        assert_eq!(queries.to_url("   "), None);
+    }
+
+    #[test]
+    fn finds_hosts_of_arguments() {
+        assert!(has_host("example.com"));
+        assert!(has_host("docs.rs/gio"));
+        assert!(has_host("localhost:3000"));
+        assert!(has_host("192.168.1.1:8080/admin"));
+        assert!(!has_host("notes.txt"));
+        assert!(!has_host("report.PDF"));
+        assert!(!has_host("version1.2"));
+        assert!(!has_host("notes"));
+        assert!(!has_host("example.com:http"));
+    }
+
+    #[test]
+    fn parses_engines() {
+        assert_eq!(
+            parse_engines("  ddg   https://duckduckgo.com/?q=%s \n\nw https://en.wikipedia.org/w/index.php?search=%s"),
+            Some(HashMap::from([
+                (String::from("ddg"), String::from("https://duckduckgo.com/?q=%s")),
+                (String::from("w"), String::from("https://en.wikipedia.org/w/index.php?search=%s")),
+            ])),
+        );
+        assert_eq!(parse_engines(""), Some(HashMap::new()));
+        assert_eq!(parse_engines("ddg https://duckduckgo.com/"), None);
+        assert_eq!(parse_engines("https://duckduckgo.com/?q=%s"), None);
+    }
+
+    #[test]
+    fn parses_templates() {
+        assert_eq!(
+            parse_templates("[A-Z]+ [0-9]+ https://example.com/\\0\n"),
+            Some(vec![(String::from("[A-Z]+ [0-9]+"), String::from("https://example.com/\\0"))]),
+        );
+        assert_eq!(parse_templates("([A-Z]+ https://example.com/\\1"), None);
+        assert_eq!(parse_templates("https://example.com/"), None);
+        let templates = parse_templates(&format_templates(&queries().templates)).unwrap();
+        assert_eq!(templates, queries().templates);
+    }
+}
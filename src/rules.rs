@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use gio::{glib, AppInfo, File, Settings};
+use gio::glib::variant::FromVariant;
+use itertools::Itertools;
+
+use crate::groups;
+use crate::launch::LaunchOptions;
+use crate::mail;
+use crate::processes::RunningProcesses;
+use crate::sources::Source;
+
+
+/// Returns the scheme and host part of an url, e.g. `https://example.com`, domain rules are matched by it.
+pub fn domain_of(uri: &str) -> String {
+    uri.split('/').take(3).collect::<Vec<&str>>().join("/")
+}
+
+
+pub fn is_web_url(file: &File) -> bool {
+    file.uri_scheme().is_some_and(|scheme| scheme == "http" || scheme == "https")
+}
+
+
+/// Finds the browser set as a default for the domain of `file`.
+pub fn default_browser(settings: &Settings, browsers: &[AppInfo], file: &File) -> Option<usize> {
+    if !is_web_url(file) {
+        return None;
+    }
+    let defaults: HashMap<String, Vec<String>> = settings.get("browsers-default-for-domains");
+    let domain = domain_of(&file.uri());
+    browsers.iter().position(|browser| {
+        defaults.get::<String>(&browser.name().into()).is_some_and(|domains| domains.contains(&domain))
+    })
+}
+
+
+/// Sets `browser` as a default for `domain`.
+pub fn remember(settings: &Settings, browser: &AppInfo, domain: String) {
+    let mut defaults: HashMap<String, Vec<String>> = settings.get("browsers-default-for-domains");
+    let domains = defaults.entry(browser.name().into()).or_default();
+    if !domains.contains(&domain) {
+        domains.push(domain);
+    }
+    let _ = settings.set("browsers-default-for-domains", defaults);
+}
+
+
+/// Conditions on the local time when a rule applies, an empty list of a kind means no restriction.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schedule {
+    /// Days of week from 1 for Monday to 7 for Sunday.
+    pub days: Vec<u32>,
+    /// Ranges of minutes since midnight with an exclusive end, a range ending before its start
+    /// goes past midnight and belongs to the day it starts on.
+    pub times: Vec<(u32, u32)>,
+    /// Inclusive ranges of dates formatted as `YYYY-MM-DD`.
+    pub dates: Vec<(String, String)>,
+}
+
+
+impl Schedule {
+    pub fn is_always(&self) -> bool {
+        self.days.is_empty() && self.times.is_empty() && self.dates.is_empty()
+    }
+
+    /// Whether the day and the date of `time` are allowed, times of day aside.
+    fn allows_day(&self, time: &glib::DateTime) -> bool {
+        let Ok(date) = time.format("%Y-%m-%d") else { return false };
+        let date_allowed = self.dates.is_empty()
+            || self.dates.iter().any(|(start, end)| start.as_str() <= date.as_str() && date.as_str() <= end.as_str());
+        date_allowed && (self.days.is_empty() || self.days.contains(&(time.day_of_week() as u32)))
+    }
+
+    pub fn matches(&self, time: &glib::DateTime) -> bool {
+        let minute = (time.hour() * 60 + time.minute()) as u32;
+        if self.times.is_empty() {
+            return self.allows_day(time);
+        }
+        let started_yesterday = || time.add_days(-1).is_ok_and(|yesterday| self.allows_day(&yesterday));
+        self.times.iter().any(|&(start, end)| match start <= end {
+            true => start <= minute && minute < end && self.allows_day(time),
+            false => (minute >= start && self.allows_day(time)) || (minute < end && started_yesterday()),
+        })
+    }
+
+    /// Formats time ranges as `09:00-17:00, 18:00-20:00`.
+    pub fn format_times(&self) -> String {
+        let format = |minutes: u32| format!("{:02}:{:02}", minutes / 60, minutes % 60);
+        self.times.iter().map(|(start, end)| format!("{}-{}", format(*start), format(*end))).join(", ")
+    }
+
+    /// Formats date ranges as `2026-12-24..2027-01-06, 2027-05-01`.
+    pub fn format_dates(&self) -> String {
+        self.dates.iter()
+            .map(|(start, end)| if start == end { start.clone() } else { format!("{start}..{end}") })
+            .join(", ")
+    }
+}
+
+
+fn parse_time(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.trim().split_once(':')?;
+    let (hours, minutes): (u32, u32) = (hours.parse().ok()?, minutes.parse().ok()?);
+    (hours <= 24 && minutes < 60 && hours * 60 + minutes <= 24 * 60).then_some(hours * 60 + minutes)
+}
+
+
+fn parse_date(text: &str) -> Option<String> {
+    let mut parts = text.trim().splitn(3, '-').map(|part| part.parse::<i32>().ok());
+    let (year, month, day) = (parts.next()??, parts.next()??, parts.next()??);
+    glib::DateTime::from_local(year, month, day, 0, 0, 0.0).ok()?;
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+
+/// Parses comma separated ranges like `09:00-17:00`, returns `None` if any of them is invalid.
+pub fn parse_times(text: &str) -> Option<Vec<(u32, u32)>> {
+    text.split(',')
+        .filter(|range| !range.trim().is_empty())
+        .map(|range| {
+            let (start, end) = range.split_once('-')?;
+            Some((parse_time(start)?, parse_time(end)?))
+        })
+        .collect()
+}
+
+
+/// Parses comma separated dates and ranges like `2026-12-24..2027-01-06`, returns `None` if any of them is invalid.
+pub fn parse_dates(text: &str) -> Option<Vec<(String, String)>> {
+    text.split(',')
+        .filter(|range| !range.trim().is_empty())
+        .map(|range| match range.split_once("..") {
+            Some((start, end)) => Some((parse_date(start)?, parse_date(end)?)),
+            None => parse_date(range).map(|date| (date.clone(), date)),
+        })
+        .collect()
+}
+
+
+/// Opens urls of a domain and its subdomains in a browser while the schedule matches, a rule
+/// with a source and without a domain matches all urls from the source. Mail rules match only
+/// `mailto:` links by the domain of the recipient, all of them without a domain.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rule {
+    /// Host name like `meet.google.com`.
+    pub domain: String,
+    /// Name of the application urls come from, empty matches any source.
+    pub source: String,
+    /// Id of a desktop file of a browser or a group, see [`groups::target`].
+    pub target: String,
+    pub schedule: Schedule,
+    /// Applies only while the target browser has a running process.
+    pub running: bool,
+    /// Matches `mailto:` links instead of web links.
+    pub mail: bool,
+    /// Added on top of the launch options of the target browser.
+    pub launch: LaunchOptions,
+}
+
+
+impl Rule {
+    fn from_dict(dict: &HashMap<String, glib::Variant>) -> Self {
+        fn get<T: FromVariant + Default>(dict: &HashMap<String, glib::Variant>, key: &str) -> T {
+            dict.get(key).and_then(|value| value.get()).unwrap_or_default()
+        }
+        Rule {
+            domain: get(dict, "domain"),
+            source: get(dict, "source"),
+            target: get(dict, "target"),
+            schedule: Schedule {
+                days: get(dict, "days"),
+                times: get(dict, "times"),
+                dates: get(dict, "dates"),
+            },
+            running: get(dict, "running"),
+            mail: get(dict, "mail"),
+            launch: LaunchOptions {
+                private: get(dict, "private"),
+                app_mode: get(dict, "app-mode"),
+                throwaway: get(dict, "throwaway"),
+                app_profile: false,
+                container: get(dict, "container"),
+                wrapper: get(dict, "wrapper"),
+                args: get(dict, "arguments"),
+                env: get(dict, "environment"),
+                webmail: get(dict, "webmail"),
+            },
+        }
+    }
+
+    fn to_dict(&self) -> HashMap<String, glib::Variant> {
+        HashMap::from([
+            (String::from("domain"), self.domain.to_variant()),
+            (String::from("source"), self.source.to_variant()),
+            (String::from("target"), self.target.to_variant()),
+            (String::from("days"), self.schedule.days.to_variant()),
+            (String::from("times"), self.schedule.times.to_variant()),
+            (String::from("dates"), self.schedule.dates.to_variant()),
+            (String::from("running"), self.running.to_variant()),
+            (String::from("mail"), self.mail.to_variant()),
+            (String::from("private"), self.launch.private.to_variant()),
+            (String::from("app-mode"), self.launch.app_mode.to_variant()),
+            (String::from("throwaway"), self.launch.throwaway.to_variant()),
+            (String::from("container"), self.launch.container.to_variant()),
+            (String::from("wrapper"), self.launch.wrapper.to_variant()),
+            (String::from("arguments"), self.launch.args.to_variant()),
+            (String::from("environment"), self.launch.env.to_variant()),
+            (String::from("webmail"), self.launch.webmail.to_variant()),
+        ])
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        let domain = self.domain.trim().trim_end_matches('.').to_lowercase();
+        let domain = domain.split("://").last().unwrap_or_default().trim_end_matches('/');
+        match domain.is_empty() {
+            true => self.mail || !self.source.trim().is_empty(),
+            false => host == domain || host.strip_suffix(domain).is_some_and(|rest| rest.ends_with('.')),
+        }
+    }
+
+    fn matches_source(&self, source: Option<&Source>) -> bool {
+        self.source.trim().is_empty() || source.is_some_and(|source| source.matches(&self.source))
+    }
+}
+
+
+pub fn load_rules(settings: &Settings) -> Vec<Rule> {
+    let rules: Vec<HashMap<String, glib::Variant>> = settings.get("rules");
+    rules.iter().map(Rule::from_dict).collect()
+}
+
+
+pub fn save_rules(settings: &Settings, rules: &[Rule]) {
+    let rules: Vec<HashMap<String, glib::Variant>> = rules.iter().map(Rule::to_dict).collect();
+    let _ = settings.set("rules", rules);
+}
+
+
+fn host_of(uri: &str) -> Option<String> {
+    glib::Uri::parse(uri, glib::UriFlags::NONE).ok()?.host().map(|host| host.to_lowercase())
+}
+
+
+/// Finds the browsers `file` opened from `source` should be opened in without showing the picker
+/// and the launch options of the rule, rules are tried in order before the remembered domains.
+/// `mailto:` links are matched only by mail rules.
+pub fn matching_browsers(settings: &Settings, browsers: &[AppInfo], file: &File, source: Option<&Source>) -> Option<(Vec<usize>, LaunchOptions)> {
+    let is_mail = mail::is_mailto(file);
+    let host = match is_mail {
+        true => mail::recipient_domain(file).unwrap_or_default(),
+        false if is_web_url(file) => host_of(&file.uri()).unwrap_or_default(),
+        false => return None,
+    };
+    let now = glib::DateTime::now_local().ok();
+    let rules = load_rules(settings);
+    let running = rules.iter().any(|rule| rule.running).then(RunningProcesses::scan);
+    rules.iter()
+        .filter(|rule| rule.mail == is_mail && rule.matches_host(&host) && rule.matches_source(source))
+        .filter(|rule| now.as_ref().is_some_and(|now| rule.schedule.matches(now)))
+        .map(|rule| (rule, groups::resolve(settings, browsers, &rule.target)))
+        .filter(|(_, numbers)| !numbers.is_empty())
+        .find(|(rule, numbers)| {
+            !rule.running || running.as_ref().is_some_and(|running| numbers.iter().any(|number| running.is_running(&browsers[*number])))
+        })
+        .map(|(rule, numbers)| (numbers, rule.launch.clone()))
+        .or_else(|| default_browser(settings, browsers, file).map(|number| (vec![number], LaunchOptions::default())))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(year: i32, month: i32, day: i32, hour: i32, minute: i32) -> glib::DateTime {
+        glib::DateTime::from_local(year, month, day, hour, minute, 0.0).unwrap()
+    }
+
+    #[test]
+    fn matches_hosts() {
+        let rule = |domain: &str, source: &str, mail: bool| Rule { domain: domain.to_string(), source: source.to_string(), mail, ..Rule::default() };
+        assert!(rule("example.com", "", false).matches_host("example.com"));
+        assert!(rule("https://Example.com/", "", false).matches_host("docs.example.com"));
+        assert!(!rule("example.com", "", false).matches_host("badexample.com"));
+        assert!(!rule("", "", false).matches_host("example.com"));
+        assert!(rule("", "slack", false).matches_host("example.com"));
+        assert!(rule("", "", true).matches_host("example.com"));
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_times("09:00-17:00, 22:30-06:00"), Some(vec![(540, 1020), (1350, 360)]));
+        assert_eq!(parse_times("00:00-24:00"), Some(vec![(0, 1440)]));
+        assert_eq!(parse_times(" "), Some(Vec::new()));
+        assert_eq!(parse_times("9-17"), None);
+        assert_eq!(parse_times("09:00-24:30"), None);
+        assert_eq!(parse_times("09:60-10:00"), None);
+        assert_eq!(parse_times("09:00-17:00, later"), None);
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(
+            parse_dates("2026-12-24..2027-01-06, 2027-5-1"),
+            Some(vec![
+                (String::from("2026-12-24"), String::from("2027-01-06")),
+                (String::from("2027-05-01"), String::from("2027-05-01")),
+            ]),
+        );
+        assert_eq!(parse_dates(""), Some(Vec::new()));
+        assert_eq!(parse_dates("2026-02-30"), None);
+        assert_eq!(parse_dates("2026-12-24..soon"), None);
+        assert_eq!(parse_dates("24.12.2026"), None);
+    }
+
+    #[test]
+    fn matches_days_and_times() {
+        // 2024-01-01 is a Monday
+        let working_hours = Schedule { days: vec![1, 2, 3, 4, 5], times: vec![(540, 1020)], dates: Vec::new() };
+        assert!(working_hours.matches(&local(2024, 1, 1, 9, 0)));
+        assert!(working_hours.matches(&local(2024, 1, 5, 16, 59)));
+        assert!(!working_hours.matches(&local(2024, 1, 1, 17, 0)));
+        assert!(!working_hours.matches(&local(2024, 1, 6, 10, 0)));
+
+        let weekend = Schedule { days: vec![6, 7], times: Vec::new(), dates: Vec::new() };
+        assert!(weekend.matches(&local(2024, 1, 7, 23, 0)));
+        assert!(!weekend.matches(&local(2024, 1, 8, 0, 0)));
+    }
+
+    #[test]
+    fn matches_times_past_midnight_on_the_day_they_start() {
+        let friday_night = Schedule { days: vec![5], times: vec![(1320, 360)], dates: Vec::new() };
+        assert!(friday_night.matches(&local(2024, 1, 5, 23, 0)));
+        assert!(friday_night.matches(&local(2024, 1, 6, 5, 59)));
+        assert!(!friday_night.matches(&local(2024, 1, 6, 6, 0)));
+        assert!(!friday_night.matches(&local(2024, 1, 6, 23, 0)));
+        assert!(!friday_night.matches(&local(2024, 1, 5, 5, 0)));
+
+        let late_friday = Schedule { days: vec![5], times: vec![(1320, 120)], dates: Vec::new() };
+        assert!(late_friday.matches(&local(2024, 1, 6, 1, 0)));
+        assert!(!late_friday.matches(&local(2024, 1, 5, 1, 0)));
+
+        let new_years_eve = Schedule {
+            days: Vec::new(),
+            times: vec![(1320, 120)],
+            dates: vec![(String::from("2024-12-31"), String::from("2024-12-31"))],
+        };
+        assert!(new_years_eve.matches(&local(2024, 12, 31, 23, 0)));
+        assert!(new_years_eve.matches(&local(2025, 1, 1, 1, 0)));
+        assert!(!new_years_eve.matches(&local(2024, 12, 31, 1, 0)));
+        assert!(!new_years_eve.matches(&local(2025, 1, 1, 23, 0)));
+    }
+
+    #[test]
+    fn matches_dates() {
+        let holidays = Schedule {
+            days: Vec::new(),
+            times: Vec::new(),
+            dates: vec![(String::from("2024-12-24"), String::from("2025-01-06"))],
+        };
+        assert!(holidays.matches(&local(2024, 12, 24, 0, 0)));
+        assert!(holidays.matches(&local(2025, 1, 6, 23, 59)));
+        assert!(!holidays.matches(&local(2025, 1, 7, 0, 0)));
+        assert!(Schedule::default().matches(&local(2025, 1, 7, 0, 0)));
+    }
+}
@@ -0,0 +1,58 @@
+/// Scores how well `query` fuzzy matches `candidate`, higher is better.
+///
+/// Every character of the query has to be found in the candidate in the same order, matches of
+/// consecutive characters and matches at the start of words score more. Returns `None` when the
+/// candidate does not match at all.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for query_char in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + candidate.get(position..)?.iter().position(|c| *c == query_char)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !candidate[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_prefixes_over_substrings() {
+        let prefix = fuzzy_score("fire", "Firefox").unwrap();
+        let word_start = fuzzy_score("fire", "Tor Firefox").unwrap();
+        let substring = fuzzy_score("fox", "Firefox").unwrap();
+        let scattered = fuzzy_score("fx", "Firefox").unwrap();
+        assert!(prefix > fuzzy_score("iref", "Firefox").unwrap());
+        assert_eq!(prefix, word_start);
+        assert!(fuzzy_score("fox", "Fox Browser").unwrap() > substring);
+        assert!(substring > scattered);
+    }
+
+    #[test]
+    fn ignores_case_and_spaces() {
+        assert_eq!(fuzzy_score("CHROME", "google chrome"), fuzzy_score("chrome", "Google Chrome"));
+        assert_eq!(fuzzy_score("go ch", "Google Chrome"), fuzzy_score("goch", "Google Chrome"));
+        assert_eq!(fuzzy_score("", "Firefox"), Some(0));
+    }
+
+    #[test]
+    fn does_not_match_missing_characters() {
+        assert_eq!(fuzzy_score("chromex", "Chromium"), None);
+        assert_eq!(fuzzy_score("xf", "Firefox"), None);
+        // characters have to be found in the same order
+        assert_eq!(fuzzy_score("of", "Foo"), None);
+        assert_eq!(fuzzy_score("a", ""), None);
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use gio::prelude::*;
+use gio::Settings;
+
+
+/// Prefix of the hint of the `open` signal carrying the source to the primary instance.
+const HINT_PREFIX: &str = "browdi-source:";
+
+/// Programs that only pass a link on, the source is the first process up the tree that is not one of them.
+/// Names are compared with `/proc/<pid>/comm` which the kernel cuts to 15 characters, hence `gio-launch-desk`
+/// for `gio-launch-desktop`.
+const HELPERS: [&str; 18] = [
+    "xdg-open", "gio", "gio-launch-desk", "gvfs-open", "kde-open", "kde-open5", "kde-open6",
+    "kioclient", "kioclient5", "exo-open", "flatpak-spawn", "sh", "bash", "dash", "zsh", "fish",
+    "env", "browdi",
+];
+
+/// How many parents are inspected before giving up.
+const MAX_DEPTH: usize = 8;
+
+/// Longest countdown in seconds, the same for the overrides and `countdown-seconds` in the preferences.
+pub const MAX_COUNTDOWN_SECONDS: u32 = 600;
+
+
+/// Application links were opened from, it goes by several names, e.g. a process name,
+/// a desktop file id and a flatpak id, and a rule may use any of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Source {
+    pub names: Vec<String>,
+}
+
+
+impl Source {
+    pub fn named(name: &str) -> Self {
+        Source { names: vec![name.to_string()] }
+    }
+
+    pub fn matches(&self, pattern: &str) -> bool {
+        let pattern = pattern.trim();
+        !pattern.is_empty() && self.names.iter().any(|name| name.eq_ignore_ascii_case(pattern))
+    }
+
+    fn add_name(&mut self, name: &str) {
+        let name = name.trim();
+        if !name.is_empty() && !self.names.iter().any(|known| known == name) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    pub fn to_hint(&self) -> String {
+        format!("{HINT_PREFIX}{}", self.names.join("\t"))
+    }
+
+    pub fn from_hint(hint: &str) -> Option<Self> {
+        let names = hint.strip_prefix(HINT_PREFIX)?;
+        let mut source = Source::default();
+        names.split('\t').for_each(|name| source.add_name(name));
+        (!source.names.is_empty()).then_some(source)
+    }
+}
+
+
+fn read_proc(pid: u32, file: &str) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/{file}")).ok()
+}
+
+
+fn parent_of(pid: u32) -> Option<u32> {
+    read_proc(pid, "status")?
+        .lines()
+        .find_map(|line| line.strip_prefix("PPid:"))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+
+/// Application ids from a systemd scope like `app-gnome-org.telegram.desktop-1234.scope` or
+/// `app-flatpak-com.slack.Slack-1234.scope`, desktops put launched apps into such scopes.
+fn scope_app_ids(cgroup: &str) -> Vec<String> {
+    let Some(scope) = cgroup.lines()
+        .filter_map(|line| line.rsplit('/').next())
+        .find_map(|unit| unit.strip_prefix("app-")?.strip_suffix(".scope")) else { return Vec::new() };
+    let scope = scope.trim_end_matches(|c: char| c.is_ascii_digit()).trim_end_matches('-');
+    let scope = scope.replace("\\x2d", "-");
+    let mut ids = vec![scope.clone()];
+    for launcher in ["flatpak-", "gnome-", "kde-", "xfce-"] {
+        if let Some(id) = scope.strip_prefix(launcher) {
+            ids.insert(0, id.to_string());
+        }
+    }
+    ids
+}
+
+
+/// Names of the process `pid` as a source.
+fn process_source(pid: u32) -> Source {
+    let mut source = Source::default();
+    if let Some(comm) = read_proc(pid, "comm") {
+        source.add_name(&comm);
+    }
+    if let Ok(exe) = fs::read_link(format!("/proc/{pid}/exe")) {
+        source.add_name(&exe.file_name().unwrap_or_default().to_string_lossy());
+    }
+    if let Ok(environ) = fs::read(format!("/proc/{pid}/environ")) {
+        let desktop_file = environ.split(|byte| *byte == 0)
+            .find_map(|variable| variable.strip_prefix(b"GIO_LAUNCHED_DESKTOP_FILE="))
+            .map(|path| String::from_utf8_lossy(path).into_owned());
+        if let Some(desktop_file) = desktop_file {
+            source.add_name(&Path::new(&desktop_file).file_stem().unwrap_or_default().to_string_lossy());
+        }
+    }
+    for id in read_proc(pid, "cgroup").map(|cgroup| scope_app_ids(&cgroup)).unwrap_or_default() {
+        source.add_name(&id);
+    }
+    source
+}
+
+
+/// Finds the application that asked to open links by walking up the process tree past
+/// `xdg-open` and similar helpers.
+pub fn detect() -> Option<Source> {
+    let mut pid = std::os::unix::process::parent_id();
+    for _ in 0..MAX_DEPTH {
+        if pid <= 1 {
+            return None;
+        }
+        let comm = read_proc(pid, "comm")?;
+        let comm = comm.trim();
+        if comm == "systemd" || comm.starts_with("dbus-") {
+            return None;
+        }
+        if !HELPERS.contains(&comm) {
+            let source = process_source(pid);
+            return (!source.names.is_empty()).then_some(source);
+        }
+        pid = parent_of(pid)?;
+    }
+    None
+}
+
+
+/// Returns file arguments from the command line of BrowDi, skipping options.
+pub fn file_arguments(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+    let mut files = Vec::new();
+    let mut args = args.skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--source") => { args.next(); },
+            Some("--") => { files.extend(args.by_ref()); },
+            Some(option) if option.starts_with('-') && option.len() > 1 => {},
+            _ => files.push(arg),
+        }
+    }
+    files
+}
+
+
+/// Seconds of the countdown for links from `source`, sources without an override use `countdown-seconds`.
+pub fn countdown_seconds(settings: &Settings, source: Option<&Source>) -> u32 {
+    let overrides: HashMap<String, u32> = settings.get("countdown-seconds-for-sources");
+    source
+        .and_then(|source| overrides.iter().find(|(name, _)| source.matches(name)))
+        .map(|(_, seconds)| *seconds)
+        .unwrap_or_else(|| settings.uint("countdown-seconds"))
+}
+
+
+/// Formats countdown overrides as `slack=0, thunderbird=5`.
+pub fn format_countdown_overrides(overrides: &HashMap<String, u32>) -> String {
+    let mut overrides: Vec<String> = overrides.iter().map(|(name, seconds)| format!("{name}={seconds}")).collect();
+    overrides.sort();
+    overrides.join(", ")
+}
+
+
+/// Parses countdown overrides like `slack=0, thunderbird=5`, returns `None` if any of them is invalid,
+/// too long or names an application given before, names are compared ignoring case as sources match them.
+pub fn parse_countdown_overrides(text: &str) -> Option<HashMap<String, u32>> {
+    let mut overrides = HashMap::new();
+    for item in text.split(',').filter(|item| !item.trim().is_empty()) {
+        let (name, seconds) = item.split_once('=')?;
+        let name = name.trim();
+        let seconds: u32 = seconds.trim().parse().ok()?;
+        let known = overrides.keys().any(|known: &String| known.eq_ignore_ascii_case(name));
+        if name.is_empty() || seconds > MAX_COUNTDOWN_SECONDS || known {
+            return None;
+        }
+        overrides.insert(name.to_string(), seconds);
+    }
+    Some(overrides)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(pairs: &[(&str, u32)]) -> HashMap<String, u32> {
+        pairs.iter().map(|(name, seconds)| (name.to_string(), *seconds)).collect()
+    }
+
+    fn args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn names_helpers_as_the_kernel_does() {
+        assert!(HELPERS.iter().all(|helper| helper.len() <= 15));
+    }
+
+    #[test]
+    fn finds_file_arguments() {
+        assert_eq!(file_arguments(args(&["browdi"]).into_iter()), args(&[]));
+        assert_eq!(
+            file_arguments(args(&["browdi", "--source", "slack", "https://example.com", "-v", "-", "file.html"]).into_iter()),
+            args(&["https://example.com", "-", "file.html"]),
+        );
+        assert_eq!(
+            file_arguments(args(&["browdi", "--", "--source", "-v"]).into_iter()),
+            args(&["--source", "-v"]),
+        );
+        assert_eq!(file_arguments(args(&["browdi", "--source"]).into_iter()), args(&[]));
+    }
+
+    #[test]
+    fn finds_app_ids_of_scopes() {
+        let app_slice = "0::/user.slice/user-1000.slice/user@1000.service/app.slice";
+        assert_eq!(
+            scope_app_ids(&format!("{app_slice}/app-gnome-org.telegram.desktop-1234.scope\n")),
+            vec!["org.telegram.desktop", "gnome-org.telegram.desktop"],
+        );
+        assert_eq!(
+            scope_app_ids(&format!("{app_slice}/app-flatpak-com.slack.Slack-5678.scope")),
+            vec!["com.slack.Slack", "flatpak-com.slack.Slack"],
+        );
+        assert_eq!(scope_app_ids(&format!("{app_slice}/app-org.gnome.Terminal\\x2dserver-42.scope")), vec!["org.gnome.Terminal-server"]);
+        assert!(scope_app_ids("0::/user.slice/user-1000.slice/session-2.scope").is_empty());
+        assert!(scope_app_ids("").is_empty());
+    }
+
+    #[test]
+    fn passes_sources_in_hints() {
+        let source = Source { names: vec![String::from("slack"), String::from("com.slack.Slack")] };
+        assert_eq!(Source::from_hint(&source.to_hint()), Some(source));
+        assert_eq!(Source::from_hint("browdi-source:"), None);
+        assert_eq!(Source::from_hint("slack"), None);
+        assert_eq!(Source::from_hint("browdi-source: slack \t\tslack"), Some(Source::named("slack")));
+    }
+
+    #[test]
+    fn matches_any_name_ignoring_case() {
+        let source = Source { names: vec![String::from("slack"), String::from("com.slack.Slack")] };
+        assert!(source.matches(" Slack "));
+        assert!(source.matches("COM.SLACK.SLACK"));
+        assert!(!source.matches("sla"));
+        assert!(!source.matches(""));
+    }
+
+    #[test]
+    fn parses_countdown_overrides() {
+        assert_eq!(parse_countdown_overrides(""), Some(HashMap::new()));
+        assert_eq!(parse_countdown_overrides(" slack = 0 , thunderbird=5,"), Some(overrides(&[("slack", 0), ("thunderbird", 5)])));
+        assert_eq!(parse_countdown_overrides("slack=600"), Some(overrides(&[("slack", 600)])));
+    }
+
+    #[test]
+    fn rejects_invalid_countdown_overrides() {
+        for text in ["slack", "slack=", "=5", "slack=five", "slack=-1", "slack=1.5", "slack=5=6", "slack=601", "slack=99999999999"] {
+            assert_eq!(parse_countdown_overrides(text), None, "{text}");
+        }
+        assert_eq!(parse_countdown_overrides("slack=1, thunderbird=2, Slack=3"), None);
+    }
+
+    #[test]
+    fn formats_parsed_countdown_overrides() {
+        let parsed = overrides(&[("thunderbird", 5), ("slack", 0)]);
+        assert_eq!(format_countdown_overrides(&parsed), "slack=0, thunderbird=5");
+        assert_eq!(parse_countdown_overrides(&format_countdown_overrides(&parsed)), Some(parsed));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+
+use gio::prelude::*;
+use gio::{glib, Settings};
+use itertools::Itertools;
+
+
+/// Number of the most recent choices remembered for a domain.
+const MAX_CHOICES_PER_DOMAIN: usize = 20;
+/// Number of domains remembered, the ones chosen for the longest time ago are dropped first.
+const MAX_DOMAINS: usize = 500;
+
+
+/// Browsers chosen manually for a domain as pairs of a browser id and unix time of the choice, oldest first.
+pub type Choices = HashMap<String, Vec<(String, i64)>>;
+
+
+pub fn load(settings: &Settings) -> Choices {
+    settings.get("domain-choices")
+}
+
+
+/// Remembers that the browser `browser_id` was chosen for `domain` in the picker.
+pub fn record_choice(settings: &Settings, domain: &str, browser_id: &str) {
+    let mut choices = load(settings);
+    add_choice(&mut choices, domain, browser_id, now());
+    let _ = settings.set("domain-choices", choices);
+}
+
+
+fn now() -> i64 {
+    glib::DateTime::now_local().map(|time| time.to_unix()).unwrap_or_default()
+}
+
+
+/// Adds a choice made at `now` and forgets the oldest choices and domains over the limits.
+fn add_choice(choices: &mut Choices, domain: &str, browser_id: &str, now: i64) {
+    let domain_choices = choices.entry(domain.to_string()).or_default();
+    domain_choices.push((browser_id.to_string(), now));
+    let excess = domain_choices.len().saturating_sub(MAX_CHOICES_PER_DOMAIN);
+    domain_choices.drain(..excess);
+    if choices.len() > MAX_DOMAINS {
+        let stale: Vec<String> = choices.iter()
+            .sorted_by_key(|(_, domain_choices)| domain_choices.last().map(|(_, time)| *time))
+            .take(choices.len() - MAX_DOMAINS)
+            .map(|(domain, _)| domain.clone())
+            .collect();
+        for domain in stale {
+            choices.remove(&domain);
+        }
+    }
+}
+
+
+/// Returns the id of the browser chosen most often for `domain`, the most recent one wins a tie.
+pub fn suggested_browser(choices: &Choices, domain: &str) -> Option<String> {
+    let domain_choices = choices.get(domain)?;
+    let mut counts: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (position, (browser_id, _)) in domain_choices.iter().enumerate() {
+        let count = counts.entry(browser_id).or_default();
+        count.0 += 1;
+        count.1 = position;
+    }
+    counts.into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(browser_id, _)| browser_id.to_string())
+}
+
+
+/// Returns the id of the browser chosen for `domain` the last `times` times in a row.
+pub fn consistent_browser(choices: &Choices, domain: &str, times: usize) -> Option<String> {
+    let domain_choices = choices.get(domain)?;
+    if times == 0 || domain_choices.len() < times {
+        return None;
+    }
+    domain_choices[domain_choices.len() - times..].iter()
+        .map(|(browser_id, _)| browser_id)
+        .all_equal_value()
+        .ok()
+        .cloned()
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BrowserOrder {
+    #[default]
+    Alphabetical,
+    /// Most frequently and recently chosen browsers first.
+    Frecency,
+    /// Same as frecency, but choices made for the domain of the current url come first.
+    DomainFrecency,
+}
+
+
+impl BrowserOrder {
+    pub const ALL: [BrowserOrder; 3] = [Self::Alphabetical, Self::Frecency, Self::DomainFrecency];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::Alphabetical => "alphabetical",
+            Self::Frecency => "frecency",
+            Self::DomainFrecency => "domain-frecency",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Alphabetical => "Alphabetical",
+            Self::Frecency => "Most used first",
+            Self::DomainFrecency => "Most used for the domain first",
+        }
+    }
+
+    pub fn load(settings: &Settings) -> Self {
+        let id = settings.string("browser-order");
+        Self::ALL.into_iter().find(|order| order.id() == id).unwrap_or_default()
+    }
+
+    pub fn save(&self, settings: &Settings) {
+        let _ = settings.set_string("browser-order", self.id());
+    }
+}
+
+
+/// Weight of a choice made `age` seconds ago, recent choices weigh more.
+fn recency_weight(age: i64) -> u64 {
+    const DAY: i64 = 24 * 60 * 60;
+    match age {
+        age if age < 4 * DAY => 100,
+        age if age < 14 * DAY => 70,
+        age if age < 31 * DAY => 50,
+        age if age < 90 * DAY => 30,
+        _ => 10,
+    }
+}
+
+
+/// Sums recency weights of choices per browser id at `now`, only choices for `domain` are counted when it is given.
+fn frecency(choices: &Choices, domain: Option<&str>, now: i64) -> HashMap<String, u64> {
+    let mut scores = HashMap::new();
+    let domain_choices = choices.iter()
+        .filter(|(choices_domain, _)| domain.is_none_or(|domain| domain == choices_domain.as_str()))
+        .flat_map(|(_, domain_choices)| domain_choices.iter());
+    for (browser_id, time) in domain_choices {
+        *scores.entry(browser_id.clone()).or_default() += recency_weight(now - time);
+    }
+    scores
+}
+
+
+/// Returns ids of `browsers` in the configured order, `domain` is the domain of the current url if any.
+pub fn order(settings: &Settings, browsers: &[gio::AppInfo], domain: Option<&str>) -> Vec<String> {
+    let ids: Vec<String> = browsers.iter()
+        .sorted_by(|a, b| Ord::cmp(&a.name(), &b.name()))
+        .filter_map(|browser| browser.id().map(String::from))
+        .collect();
+    sort_ids(ids, &load(settings), BrowserOrder::load(settings), domain, now())
+}
+
+
+/// Sorts browser `ids` given in alphabetical order by `order` of `choices` made before `now`.
+fn sort_ids(ids: Vec<String>, choices: &Choices, order: BrowserOrder, domain: Option<&str>, now: i64) -> Vec<String> {
+    let ids = ids.into_iter();
+    match order {
+        BrowserOrder::Alphabetical => ids.collect(),
+        BrowserOrder::Frecency => {
+            let scores = frecency(choices, None, now);
+            ids.sorted_by_key(|id| std::cmp::Reverse(scores.get(id).copied().unwrap_or_default())).collect()
+        },
+        BrowserOrder::DomainFrecency => {
+            let scores = frecency(choices, None, now);
+            let domain_scores = domain.map(|domain| frecency(choices, Some(domain), now)).unwrap_or_default();
+            ids.sorted_by_key(|id| std::cmp::Reverse((
+                domain_scores.get(id).copied().unwrap_or_default(),
+                scores.get(id).copied().unwrap_or_default(),
+            ))).collect()
+        },
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+    const NOW: i64 = 1000 * DAY;
+
+    /// Choices of browsers for domains made in the given order, a minute apart and ending `NOW`.
+    fn choices(made: &[(&str, &str)]) -> Choices {
+        let mut choices = Choices::new();
+        for (index, (domain, browser_id)) in made.iter().enumerate() {
+            add_choice(&mut choices, domain, browser_id, NOW - (made.len() - index) as i64 * 60);
+        }
+        choices
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn suggests_the_most_chosen_browser() {
+        let choices = choices(&[
+            ("example.com", "firefox"), ("example.com", "chromium"), ("example.com", "firefox"), ("example.com", "epiphany"),
+            ("other.org", "chromium"),
+        ]);
+        assert_eq!(suggested_browser(&choices, "example.com").as_deref(), Some("firefox"));
+        assert_eq!(suggested_browser(&choices, "other.org").as_deref(), Some("chromium"));
+        assert_eq!(suggested_browser(&choices, "unknown.net"), None);
+    }
+
+    #[test]
+    fn breaks_ties_with_the_latest_choice() {
+        let choices = choices(&[("example.com", "firefox"), ("example.com", "chromium"), ("example.com", "chromium"), ("example.com", "firefox")]);
+        assert_eq!(suggested_browser(&choices, "example.com").as_deref(), Some("firefox"));
+    }
+
+    #[test]
+    fn finds_browsers_chosen_in_a_row() {
+        let choices = choices(&[("example.com", "chromium"), ("example.com", "firefox"), ("example.com", "firefox"), ("example.com", "firefox")]);
+        assert_eq!(consistent_browser(&choices, "example.com", 3).as_deref(), Some("firefox"));
+        assert_eq!(consistent_browser(&choices, "example.com", 4), None);
+        assert_eq!(consistent_browser(&choices, "example.com", 5), None);
+        assert_eq!(consistent_browser(&choices, "example.com", 0), None);
+        assert_eq!(consistent_browser(&choices, "unknown.net", 1), None);
+    }
+
+    #[test]
+    fn forgets_old_choices_and_domains() {
+        let mut choices = Choices::new();
+        for index in 0..MAX_CHOICES_PER_DOMAIN + 5 {
+            add_choice(&mut choices, "example.com", &index.to_string(), index as i64);
+        }
+        assert_eq!(choices["example.com"].len(), MAX_CHOICES_PER_DOMAIN);
+        assert_eq!(choices["example.com"][0].0, "5");
+        for index in 0..MAX_DOMAINS {
+            add_choice(&mut choices, &format!("{index}.example.org"), "firefox", 100 + index as i64);
+        }
+        assert_eq!(choices.len(), MAX_DOMAINS);
+        assert!(!choices.contains_key("example.com"));
+        assert!(choices.contains_key("0.example.org"));
+    }
+
+    #[test]
+    fn weighs_recent_choices_more() {
+        let mut choices = Choices::new();
+        for age in [100 * DAY, 100 * DAY, 100 * DAY] {
+            add_choice(&mut choices, "example.com", "firefox", NOW - age);
+        }
+        add_choice(&mut choices, "example.com", "chromium", NOW - DAY);
+        let scores = frecency(&choices, None, NOW);
+        assert_eq!(scores["firefox"], 30);
+        assert_eq!(scores["chromium"], 100);
+    }
+
+    #[test]
+    fn sorts_browsers() {
+        let choices = choices(&[
+            ("example.com", "epiphany"), ("other.org", "chromium"), ("other.org", "chromium"), ("third.net", "chromium"),
+        ]);
+        let all = ids(&["chromium", "epiphany", "firefox"]);
+        assert_eq!(sort_ids(all.clone(), &choices, BrowserOrder::Alphabetical, Some("example.com"), NOW), all);
+        assert_eq!(sort_ids(all.clone(), &choices, BrowserOrder::Frecency, Some("example.com"), NOW), all);
+        assert_eq!(
+            sort_ids(all.clone(), &choices, BrowserOrder::DomainFrecency, Some("example.com"), NOW),
+            ids(&["epiphany", "chromium", "firefox"]),
+        );
+        assert_eq!(sort_ids(all.clone(), &choices, BrowserOrder::DomainFrecency, None, NOW), all);
+    }
+}
@@ -0,0 +1,231 @@
+use std::net::Ipv4Addr;
+
+use gio::glib;
+
+
+/// Defanged notations of url parts, e.g. `example[.]com` in security reports.
+const DEFANGED: [(&str, &str); 7] = [
+    ("[.]", "."),
+    ("(.)", "."),
+    ("{.}", "."),
+    ("[dot]", "."),
+    ("(dot)", "."),
+    ("[:]", ":"),
+    ("[://]", "://"),
+];
+
+/// Characters left at the end of a url by the sentence around it.
+const TRAILING_PUNCTUATION: &str = ".,;:!?'\"*";
+
+
+/// Drops `close` from the end of `url` while there are more of them than `open`, so `(see https://example.com)`
+/// loses the last parenthesis while `https://en.wikipedia.org/wiki/Rust_(language)` keeps it.
+fn strip_unbalanced(url: &str, open: char, close: char) -> Option<&str> {
+    let stripped = url.strip_suffix(close)?;
+    (url.matches(close).count() > url.matches(open).count()).then_some(stripped)
+}
+
+
+/// Fixes common damage of a url copied from a document: line breaks and spaces from wrapping,
+/// punctuation of the surrounding text, angle brackets and defanged `hxxp://example[.]com` notation.
+/// A url without a scheme gets `https://`.
+pub fn repair(text: &str) -> String {
+    let mut url: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    for (defanged, fixed) in DEFANGED {
+        url = url.replace(defanged, fixed);
+    }
+    if url.get(..4).is_some_and(|scheme| scheme.eq_ignore_ascii_case("hxxp")) {
+        url.replace_range(..4, "http");
+    }
+
+    let mut url = url.trim_start_matches(['<', '(', '[', '"', '\'']);
+    loop {
+        let trimmed = url.trim_end_matches(|c| TRAILING_PUNCTUATION.contains(c)).trim_end_matches('>');
+        let trimmed = strip_unbalanced(trimmed, '(', ')')
+            .or_else(|| strip_unbalanced(trimmed, '[', ']'))
+            .unwrap_or(trimmed);
+        if trimmed == url {
+            break;
+        }
+        url = trimmed;
+    }
+
+    let host = url.split('/').next().unwrap_or_default();
+    if has_scheme(url) || url.starts_with('/') {
+        url.to_string()
+    } else if is_local(host) {
+        format!("http://{url}")
+    } else if host.contains('.') {
+        format!("https://{url}")
+    } else {
+        url.to_string()
+    }
+}
+
+
+/// Checks if `host`, with an optional port, is this computer or an IP address, they are usually
+/// served without TLS.
+fn is_local(host: &str) -> bool {
+    let name = host.split_once(':').map_or(host, |(name, _)| name);
+    name.eq_ignore_ascii_case("localhost") || name.parse::<Ipv4Addr>().is_ok()
+}
+
+
+pub fn has_scheme(url: &str) -> bool {
+    // `example.com:8080` and `localhost:3000` look like urls with the schemes `example.com` and `localhost`
+    glib::Uri::peek_scheme(url).is_some_and(|scheme| !scheme.contains('.') && scheme != "localhost")
+}
+
+
+/// Repaired url typed, pasted or dropped by the user, `None` if the text is not a link.
+pub fn link(text: &str) -> Option<String> {
+    let url = repair(text);
+    has_scheme(&url).then_some(url)
+}
+
+
+/// Links in `text` with one link per line, like a `text/uri-list` where lines starting with `#`
+/// are comments.
+pub fn links(text: &str) -> Vec<String> {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(link)
+        .collect()
+}
+
+
+/// Query parameters that only tell where a link was shared from.
+const TRACKING_PARAMETERS: [&str; 16] = [
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "twclid", "igshid", "mc_cid",
+    "mc_eid", "_hsenc", "_hsmi", "mkt_tok", "ref_src", "si",
+];
+
+/// Prefixes of families of tracking parameters, e.g. `utm_source`.
+const TRACKING_PREFIXES: [&str; 3] = ["utm_", "pk_", "oly_"];
+
+
+/// Removes tracking parameters from the query of `url`.
+pub fn clean(url: &str) -> String {
+    let (rest, fragment) = url.split_once('#').map_or((url, None), |(rest, fragment)| (rest, Some(fragment)));
+    let Some((base, query)) = rest.split_once('?') else { return url.to_string() };
+    let query = query.split('&')
+        .filter(|parameter| {
+            let name = parameter.split('=').next().unwrap_or_default().to_lowercase();
+            !name.is_empty()
+                && !TRACKING_PARAMETERS.contains(&name.as_str())
+                && !TRACKING_PREFIXES.iter().any(|prefix| name.starts_with(prefix))
+        })
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut cleaned = base.to_string();
+    if !query.is_empty() {
+        cleaned.push('?');
+        cleaned.push_str(&query);
+    }
+    if let Some(fragment) = fragment {
+        cleaned.push('#');
+        cleaned.push_str(fragment);
+    }
+    cleaned
+}
+
+
+/// Forms a url is copied to the clipboard in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyFormat {
+    Url,
+    CleanUrl,
+    Markdown,
+    Html,
+}
+
+
+impl CopyFormat {
+    pub const ALL: [CopyFormat; 4] = [CopyFormat::Url, CopyFormat::CleanUrl, CopyFormat::Markdown, CopyFormat::Html];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CopyFormat::Url => "Copy url",
+            CopyFormat::CleanUrl => "Copy url without tracking",
+            CopyFormat::Markdown => "Copy as Markdown link",
+            CopyFormat::Html => "Copy as HTML link",
+        }
+    }
+
+    pub fn shortcut(self) -> &'static str {
+        match self {
+            CopyFormat::Url => "Ctrl+C",
+            CopyFormat::CleanUrl => "Ctrl+Shift+C",
+            CopyFormat::Markdown => "Ctrl+K",
+            CopyFormat::Html => "Ctrl+Shift+K",
+        }
+    }
+
+    /// Text copied for `url`, links are titled with `title`.
+    pub fn text(self, url: &str, title: &str) -> String {
+        match self {
+            CopyFormat::Url => url.to_string(),
+            CopyFormat::CleanUrl => clean(url),
+            CopyFormat::Markdown => format!(
+                "[{}]({})",
+                title.replace('[', "\\[").replace(']', "\\]"),
+                url.replace('(', "%28").replace(')', "%29"),
+            ),
+            CopyFormat::Html => format!(
+                "<a href=\"{}\">{}</a>",
+                glib::markup_escape_text(url),
+                glib::markup_escape_text(title),
+            ),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repairs_defanged_urls() {
+        assert_eq!(repair("hxxps://example[.]com/path"), "https://example.com/path");
+        assert_eq!(repair("HXXP://example(dot)com"), "http://example.com");
+        assert_eq!(repair("hxxps[://]malware[.]example[.]org"), "https://malware.example.org");
+    }
+
+    #[test]
+    fn repairs_urls_copied_from_text() {
+        assert_eq!(repair("(https://example.com/page)."), "https://example.com/page");
+        assert_eq!(repair("<https://example.com/a>,"), "https://example.com/a");
+        assert_eq!(repair("https://example.com/a-very-\n  long/path"), "https://example.com/a-very-long/path");
+        assert_eq!(repair("\"example.com/docs\""), "https://example.com/docs");
+        assert_eq!(repair("https://en.wikipedia.org/wiki/Rust_(language)"), "https://en.wikipedia.org/wiki/Rust_(language)");
+        assert_eq!(repair("(https://en.wikipedia.org/wiki/Rust_(language))"), "https://en.wikipedia.org/wiki/Rust_(language)");
+        assert_eq!(repair("/home/user/file.txt"), "/home/user/file.txt");
+        assert_eq!(repair("localhost:3000/admin"), "http://localhost:3000/admin");
+        assert_eq!(repair("192.168.1.1:8080"), "http://192.168.1.1:8080");
+        assert_eq!(repair("example.com:8080"), "https://example.com:8080");
+    }
+
+    #[test]
+    fn finds_links() {
+        assert_eq!(link("example.com"), Some(String::from("https://example.com")));
+        assert_eq!(link("mailto:user@example.com"), Some(String::from("mailto:user@example.com")));
+        assert_eq!(link("not a link"), None);
+    }
+
+    #[test]
+    fn finds_links_on_lines() {
+        assert_eq!(
+            links("# dropped links\r\nhttps://example.com/a\r\n\r\nexample.org\nnot a link\n"),
+            ["https://example.com/a", "https://example.org"],
+        );
+    }
+
+    #[test]
+    fn removes_tracking_parameters() {
+        assert_eq!(clean("https://example.com/?utm_source=news&id=5&fbclid=abc"), "https://example.com/?id=5");
+        assert_eq!(clean("https://example.com/a?UTM_Medium=x&gclid=1#top"), "https://example.com/a#top");
+        assert_eq!(clean("https://example.com/a?q=rust&page=2#results"), "https://example.com/a?q=rust&page=2#results");
+        assert_eq!(clean("https://example.com/a"), "https://example.com/a");
+    }
+}